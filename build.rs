@@ -0,0 +1,77 @@
+//! Embeds everything under `static/` into the binary with content-hashed names.
+//!
+//! HTMX must be vendored into `static/` by `scripts/vendor-htmx.sh`: pages
+//! never load it from a CDN, so release builds fail without it and other
+//! builds warn. Its SSE extension is only needed for `--live-results`, so a
+//! missing copy is a warning in every profile.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+fn collect(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect(&path, files);
+        } else if !path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.')) {
+            files.push(path);
+        }
+    }
+}
+
+// FNV-1a: stable across builds and toolchains, which std's DefaultHasher is not
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+/// Scripts every page loads, which must be vendored.
+const REQUIRED: &[&str] = &["htmx.min.js"];
+
+/// Scripts only optional features load.
+const OPTIONAL: &[&str] = &["htmx-sse.js"];
+
+fn main() {
+    let root = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap()).join("static");
+    println!("cargo:rerun-if-changed={}", root.display());
+
+    let missing: Vec<&str> = REQUIRED.iter().copied().filter(|name| !root.join(name).is_file()).collect();
+    if !missing.is_empty() {
+        let message = format!("static/{} missing, run scripts/vendor-htmx.sh", missing.join(" and static/"));
+        if env::var("PROFILE").as_deref() == Ok("release") {
+            panic!("{}", message);
+        }
+        println!("cargo:warning={} (the web UI will not work without it)", message);
+    }
+    for name in OPTIONAL.iter().filter(|name| !root.join(name).is_file()) {
+        println!("cargo:warning=static/{} missing, so --live-results will be unavailable", name);
+    }
+
+    let mut files = Vec::new();
+    collect(&root, &mut files);
+    files.sort();
+
+    let mut table = String::from("pub static ASSETS: &[Asset] = &[\n");
+    for file in files {
+        println!("cargo:rerun-if-changed={}", file.display());
+        let path = file.strip_prefix(&root).unwrap().to_string_lossy().replace('\\', "/");
+        let hash = format!("{:016x}", fnv1a(&fs::read(&file).unwrap()));
+        let hashed_path = match path.rsplit_once('.') {
+            Some((stem, extension)) => format!("{}.{}.{}", stem, &hash[..12], extension),
+            None => format!("{}.{}", path, &hash[..12]),
+        };
+        table.push_str(&format!(
+            "    Asset {{ path: {:?}, hashed_path: {:?}, bytes: include_bytes!({:?}) }},\n",
+            path,
+            hashed_path,
+            file.display().to_string()
+        ));
+    }
+    table.push_str("];\n");
+
+    fs::write(PathBuf::from(env::var("OUT_DIR").unwrap()).join("assets.rs"), table).unwrap();
+}
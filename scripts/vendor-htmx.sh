@@ -0,0 +1,36 @@
+#!/bin/sh
+# Downloads the pinned HTMX release and its SSE extension into static/ so they
+# are embedded in the binary; pages never load scripts from a CDN. The
+# extension is only needed for --live-results and is skipped until pinned.
+set -eu
+
+VERSION=1.9.10
+INTEGRITY="sha384-D1Kt99CQMDuVetoL1lrYwg5t+9QdHe7NLX/SoJYkXDFfX37iInKRy5xLSi8nO7UC"
+# Empty until pinned. To pin it, download the file, review it, and set this to
+# the sha384 that `PIN_SSE=1 scripts/vendor-htmx.sh` prints
+SSE_INTEGRITY=""
+STATIC="$(dirname "$0")/../static"
+
+# fetch URL DEST EXPECTED_INTEGRITY
+fetch() {
+    curl -fsSL "$1" -o "$2.tmp"
+    ACTUAL="sha384-$(openssl dgst -sha384 -binary "$2.tmp" | openssl base64 -A)"
+    if [ -z "$3" ]; then
+        echo "No integrity pinned for $1 (downloaded ${ACTUAL}); review the file and pin it" >&2
+        rm -f "$2.tmp"
+        exit 1
+    elif [ "$ACTUAL" != "$3" ]; then
+        echo "Integrity check failed for $1: got ${ACTUAL}" >&2
+        rm -f "$2.tmp"
+        exit 1
+    fi
+    mv "$2.tmp" "$2"
+}
+
+fetch "https://unpkg.com/htmx.org@${VERSION}/dist/htmx.min.js" "$STATIC/htmx.min.js" "$INTEGRITY"
+if [ -n "$SSE_INTEGRITY" ] || [ -n "${PIN_SSE:-}" ]; then
+    fetch "https://unpkg.com/htmx.org@${VERSION}/dist/ext/sse.js" "$STATIC/htmx-sse.js" "$SSE_INTEGRITY"
+    echo "Vendored htmx ${VERSION} and its SSE extension into static/"
+else
+    echo "Vendored htmx ${VERSION} into static/; the SSE extension has no pinned integrity, so --live-results is unavailable"
+fi
@@ -0,0 +1,431 @@
+//! Tamper-evident audit log of conversions.
+//!
+//! Each conversion is appended to the `audit_log` table with the SHA-256 hash
+//! of the previous entry, so editing or removing any entry breaks the chain
+//! from that point on. `currency-converter audit verify` recomputes it.
+//!
+//! A mixed-currency conversion such as `€20 + £15 to USD` has no single
+//! source currency: it is recorded in the target currency at rate 1, with
+//! each converted term and its rate in `legs`, which are hashed with the rest.
+
+use anyhow::{Context, Result};
+use chrono::{NaiveDate, SecondsFormat, Utc};
+use rusqlite::{Connection, OptionalExtension, Row, TransactionBehavior, params};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{path::Path, sync::Mutex};
+
+use crate::{ExchangeRates, expr::Leg, format, store};
+
+/// `prev_hash` of the first entry.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS audit_log (
+        seq            INTEGER PRIMARY KEY,
+        recorded_at    TEXT    NOT NULL,
+        caller         TEXT    NOT NULL,
+        input          TEXT    NOT NULL,
+        from_currency  TEXT    NOT NULL,
+        to_currency    TEXT    NOT NULL,
+        amount         REAL    NOT NULL,
+        rate           REAL    NOT NULL,
+        result         REAL    NOT NULL,
+        rounded_result TEXT    NOT NULL,
+        rounding       TEXT    NOT NULL,
+        provider       TEXT,
+        rate_timestamp INTEGER,
+        prev_hash      TEXT    NOT NULL,
+        hash           TEXT    NOT NULL,
+        legs           TEXT
+    );
+    CREATE INDEX IF NOT EXISTS audit_log_by_time ON audit_log (recorded_at);
+";
+
+const COLUMNS: &str = "seq, recorded_at, caller, input, from_currency, to_currency, amount, rate, result, \
+                       rounded_result, rounding, provider, rate_timestamp, prev_hash, hash, legs";
+
+/// What was converted, by whom, and with which rate. Field order is part of
+/// the hash, so do not reorder.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// RFC 3339 UTC time of the conversion
+    pub recorded_at: String,
+    /// `cli:<user>` or `web:<client address>`
+    pub caller: String,
+    /// Amount as entered, e.g. `€20 + £15 to USD`
+    pub input: String,
+    pub from: String,
+    pub to: String,
+    /// Evaluated amount in `from`
+    pub amount: f64,
+    pub rate: f64,
+    /// Unrounded `amount * rate`
+    pub result: f64,
+    /// Result rounded to `to`'s minor units, as shown to the caller
+    pub rounded_result: String,
+    pub rounding: String,
+    pub provider: Option<String>,
+    /// Provider's publication time of the rates (Unix seconds); None when
+    /// the provider only reports the date the rates apply to
+    pub rate_timestamp: Option<u64>,
+    /// Terms converted into `to` before being combined. Left out of the
+    /// hash when empty, so entries recorded before legs existed still verify.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub legs: Vec<Leg>,
+}
+
+impl AuditRecord {
+    pub fn new(caller: String, input: &str, from: &str, to: &str, amount: f64, rate: f64, rates: &ExchangeRates) -> Self {
+        let result = amount * rate;
+        Self {
+            recorded_at: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            caller,
+            input: input.to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+            amount,
+            rate,
+            result,
+            rounded_result: format::rounded(result, to),
+            rounding: format!("{}, {} decimals", format::ROUNDING_MODE, format::minor_units(to)),
+            provider: rates.provider.clone(),
+            rate_timestamp: rates.timestamp,
+            legs: Vec::new(),
+        }
+    }
+
+    pub fn with_legs(mut self, legs: Vec<Leg>) -> Self {
+        self.legs = legs;
+        self
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditEntry {
+    pub seq: i64,
+    #[serde(flatten)]
+    pub record: AuditRecord,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditEntry {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            seq: row.get(0)?,
+            record: AuditRecord {
+                recorded_at: row.get(1)?,
+                caller: row.get(2)?,
+                input: row.get(3)?,
+                from: row.get(4)?,
+                to: row.get(5)?,
+                amount: row.get(6)?,
+                rate: row.get(7)?,
+                result: row.get(8)?,
+                rounded_result: row.get(9)?,
+                rounding: row.get(10)?,
+                provider: row.get(11)?,
+                rate_timestamp: row.get::<_, Option<i64>>(12)?.map(|timestamp| timestamp as u64),
+                legs: match row.get::<_, Option<String>>(15)? {
+                    Some(legs) => serde_json::from_str(&legs).map_err(|e| {
+                        rusqlite::Error::FromSqlConversionFailure(15, rusqlite::types::Type::Text, Box::new(e))
+                    })?,
+                    None => Vec::new(),
+                },
+            },
+            prev_hash: row.get(13)?,
+            hash: row.get(14)?,
+        })
+    }
+}
+
+fn chain_hash(prev_hash: &str, record: &AuditRecord) -> Result<String> {
+    let mut hasher = Sha256::new();
+    hasher.update(prev_hash.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(serde_json::to_vec(record)?);
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Filters for [`AuditLog::query`]; unset fields match everything.
+#[derive(Default)]
+pub struct AuditQuery {
+    /// First day to include (UTC)
+    pub since: Option<NaiveDate>,
+    /// Last day to include (UTC)
+    pub until: Option<NaiveDate>,
+    pub caller: Option<String>,
+    /// Entries converting from or to this currency
+    pub currency: Option<String>,
+    pub limit: usize,
+}
+
+/// Outcome of [`AuditLog::verify`].
+pub struct Verification {
+    pub entries: usize,
+    /// Hash of the last entry, to anchor the chain externally
+    pub head: String,
+    /// First entry that does not match the chain, and why
+    pub broken: Option<(i64, String)>,
+}
+
+pub struct AuditLog {
+    conn: Mutex<Connection>,
+}
+
+impl AuditLog {
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::init(store::open_connection(path)?)
+    }
+
+    #[cfg(test)]
+    pub fn in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA).context("Failed to create audit table")?;
+        // Logs created before legs were recorded lack the column
+        let has_legs = conn
+            .prepare("SELECT 1 FROM pragma_table_info('audit_log') WHERE name = 'legs'")?
+            .exists([])?;
+        if !has_legs {
+            conn.execute("ALTER TABLE audit_log ADD COLUMN legs TEXT", [])
+                .context("Failed to add legs to the audit table")?;
+        }
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Appends `record` to the chain. The write lock is taken before reading
+    /// the previous hash, so concurrent writers cannot fork the chain.
+    pub fn append(&self, record: AuditRecord) -> Result<AuditEntry> {
+        let mut conn = self.conn();
+        let transaction = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        let prev_hash: String = transaction
+            .query_row("SELECT hash FROM audit_log ORDER BY seq DESC LIMIT 1", [], |row| row.get(0))
+            .optional()?
+            .unwrap_or_else(|| GENESIS_HASH.to_string());
+        let hash = chain_hash(&prev_hash, &record)?;
+
+        transaction.execute(
+            &format!("INSERT INTO audit_log ({}) VALUES (NULL, ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)", COLUMNS),
+            params![
+                record.recorded_at,
+                record.caller,
+                record.input,
+                record.from,
+                record.to,
+                record.amount,
+                record.rate,
+                record.result,
+                record.rounded_result,
+                record.rounding,
+                record.provider,
+                record.rate_timestamp.map(|timestamp| timestamp as i64),
+                prev_hash,
+                hash,
+                (!record.legs.is_empty()).then(|| serde_json::to_string(&record.legs)).transpose()?,
+            ],
+        )?;
+        let seq = transaction.last_insert_rowid();
+        transaction.commit()?;
+
+        Ok(AuditEntry { seq, record, prev_hash, hash })
+    }
+
+    /// Entries matching `query`, oldest first.
+    pub fn query(&self, query: &AuditQuery) -> Result<Vec<AuditEntry>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(&format!(
+            "SELECT {} FROM (
+                 SELECT * FROM audit_log
+                 WHERE (?1 IS NULL OR substr(recorded_at, 1, 10) >= ?1)
+                   AND (?2 IS NULL OR substr(recorded_at, 1, 10) <= ?2)
+                   AND (?3 IS NULL OR caller = ?3)
+                   AND (?4 IS NULL OR from_currency = ?4 OR to_currency = ?4)
+                 ORDER BY seq DESC
+                 LIMIT ?5
+             ) ORDER BY seq",
+            COLUMNS
+        ))?;
+
+        let entries = statement
+            .query_map(
+                params![
+                    query.since.map(|date| date.to_string()),
+                    query.until.map(|date| date.to_string()),
+                    query.caller,
+                    query.currency.as_ref().map(|currency| currency.to_uppercase()),
+                    query.limit as i64,
+                ],
+                AuditEntry::from_row,
+            )?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(entries)
+    }
+
+    /// Walks the whole chain, recomputing every hash.
+    pub fn verify(&self) -> Result<Verification> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(&format!("SELECT {} FROM audit_log ORDER BY seq", COLUMNS))?;
+        let mut rows = statement.query([])?;
+
+        let mut verification = Verification {
+            entries: 0,
+            head: GENESIS_HASH.to_string(),
+            broken: None,
+        };
+        let mut expected_seq = 1;
+
+        while let Some(row) = rows.next()? {
+            let entry = AuditEntry::from_row(row)?;
+            let problem = if entry.seq != expected_seq {
+                Some(format!("expected entry #{}, found #{} (entries removed)", expected_seq, entry.seq))
+            } else if entry.prev_hash != verification.head {
+                Some("previous hash does not match the preceding entry".to_string())
+            } else if chain_hash(&entry.prev_hash, &entry.record)? != entry.hash {
+                Some("contents do not match the recorded hash (entry modified)".to_string())
+            } else {
+                None
+            };
+
+            if let Some(problem) = problem {
+                verification.broken = Some((entry.seq, problem));
+                return Ok(verification);
+            }
+
+            verification.entries += 1;
+            verification.head = entry.hash;
+            expected_seq += 1;
+        }
+
+        Ok(verification)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn record(input: &str) -> AuditRecord {
+        let rates = ExchangeRates {
+            success: true,
+            timestamp: Some(1_718_000_000),
+            base: Some("USD".to_string()),
+            date: None,
+            rates: HashMap::from([("EUR".to_string(), 0.925)]),
+            provider: Some("open.er-api.com".to_string()),
+            fetched_at: None,
+        };
+        AuditRecord::new("cli:test".to_string(), input, "USD", "EUR", 100.0, 0.925, &rates)
+    }
+
+    #[test]
+    fn appended_entries_form_a_valid_chain() {
+        let log = AuditLog::in_memory().unwrap();
+        let first = log.append(record("100")).unwrap();
+        let second = log.append(record("100 USD")).unwrap();
+
+        assert_eq!(first.prev_hash, GENESIS_HASH);
+        assert_eq!(second.prev_hash, first.hash);
+        assert_eq!(second.record.rounded_result, "92.50");
+
+        let verification = log.verify().unwrap();
+        assert_eq!(verification.entries, 2);
+        assert_eq!(verification.head, second.hash);
+        assert!(verification.broken.is_none());
+    }
+
+    #[test]
+    fn mixed_currency_conversions_record_each_leg() {
+        // Frankfurter reports the date of its rates but not when it published them
+        let rates = ExchangeRates {
+            success: true,
+            timestamp: None,
+            base: Some("EUR".to_string()),
+            date: Some("2024-06-10".to_string()),
+            rates: HashMap::from([("USD".to_string(), 1.1), ("GBP".to_string(), 0.8)]),
+            provider: Some("api.frankfurter.app".to_string()),
+            fetched_at: Some(1_718_000_600),
+        };
+        let input = "€20 + £15 to USD";
+        let money = crate::expr::parse(input, &crate::format::Locale::default()).unwrap().evaluate("USD", &rates).unwrap();
+        let record =
+            AuditRecord::new("cli:test".to_string(), input, "USD", "USD", money.amount, 1.0, &rates).with_legs(money.legs);
+
+        let log = AuditLog::in_memory().unwrap();
+        let entry = log.append(record).unwrap();
+        let [eur, gbp] = &entry.record.legs[..] else { panic!("expected two legs") };
+        assert_eq!((eur.currency.as_str(), eur.amount), ("EUR", 20.0));
+        assert!((eur.rate - 1.1).abs() < 1e-9);
+        assert_eq!((gbp.currency.as_str(), gbp.amount), ("GBP", 15.0));
+        assert!((gbp.rate - 1.375).abs() < 1e-9);
+        assert_eq!(entry.record.rounded_result, "42.62");
+        assert_eq!(entry.record.rate_timestamp, None);
+
+        let stored = log.query(&AuditQuery { limit: 10, ..Default::default() }).unwrap();
+        assert_eq!(stored[0].record, entry.record);
+        assert!(log.verify().unwrap().broken.is_none());
+
+        // The legs are part of the hash
+        log.conn()
+            .execute("UPDATE audit_log SET legs = replace(legs, '1.375', '1.5') WHERE seq = 1", [])
+            .unwrap();
+        assert_eq!(log.verify().unwrap().broken.map(|(seq, _)| seq), Some(1));
+    }
+
+    #[test]
+    fn logs_from_before_legs_still_verify() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(&SCHEMA.replace(",\n        legs           TEXT", "")).unwrap();
+        let record = record("100");
+        conn.execute(
+            "INSERT INTO audit_log VALUES (1, ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+            params![
+                record.recorded_at,
+                record.caller,
+                record.input,
+                record.from,
+                record.to,
+                record.amount,
+                record.rate,
+                record.result,
+                record.rounded_result,
+                record.rounding,
+                record.provider,
+                record.rate_timestamp.map(|timestamp| timestamp as i64),
+                GENESIS_HASH,
+                chain_hash(GENESIS_HASH, &record).unwrap(),
+            ],
+        )
+        .unwrap();
+
+        let log = AuditLog::init(conn).unwrap();
+        log.append(self::record("200")).unwrap();
+        let verification = log.verify().unwrap();
+        assert_eq!(verification.entries, 2);
+        assert!(verification.broken.is_none());
+    }
+
+    #[test]
+    fn verify_detects_modified_and_removed_entries() {
+        let log = AuditLog::in_memory().unwrap();
+        for input in ["1", "2", "3"] {
+            log.append(record(input)).unwrap();
+        }
+
+        log.conn().execute("UPDATE audit_log SET rate = 0.5 WHERE seq = 2", []).unwrap();
+        assert_eq!(log.verify().unwrap().broken.map(|(seq, _)| seq), Some(2));
+
+        log.conn().execute("DELETE FROM audit_log WHERE seq = 2", []).unwrap();
+        assert_eq!(log.verify().unwrap().broken.map(|(seq, _)| seq), Some(3));
+    }
+}
@@ -0,0 +1,222 @@
+//! In-memory cache of fetched rate tables, keyed by base currency.
+//!
+//! The web server persists the cache to a JSON file on shutdown (by default
+//! [`default_path`]) and loads it on startup, so a restart does not begin with
+//! a burst of provider requests.
+//! Every fetched table is also broadcast to [`RateCache::subscribe`] receivers, which
+//! is how the live rate stream learns about refreshes.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::{RwLock, broadcast};
+
+use crate::metrics::metrics;
+use crate::store::SnapshotStore;
+use crate::{ExchangeRates, MOCK_PROVIDER, currency};
+
+struct CachedRates {
+    fetched_at: Instant,
+    rates: Arc<ExchangeRates>,
+}
+
+/// On-disk form of a cache entry; `Instant`s are not serializable, so the
+/// fetch time is stored as a Unix timestamp.
+#[derive(Serialize, Deserialize)]
+struct StoredRates<R> {
+    fetched_at: u64,
+    rates: R,
+}
+
+/// `$XDG_CACHE_HOME/currency-converter/rates.json`, falling back to `~/.cache`
+/// and finally the current directory.
+pub fn default_path() -> PathBuf {
+    let cache_home = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")));
+
+    match cache_home {
+        Some(dir) => dir.join("currency-converter").join("rates.json"),
+        None => PathBuf::from("currency-converter-cache.json"),
+    }
+}
+
+pub struct RateCache {
+    api_key: String,
+    ttl: Duration,
+    entries: RwLock<HashMap<String, CachedRates>>,
+    store: Option<Arc<SnapshotStore>>,
+    updates: broadcast::Sender<Arc<ExchangeRates>>,
+}
+
+/// Fetched tables buffered per subscriber; a slower one skips ahead to the newest.
+const UPDATE_CAPACITY: usize = 64;
+
+impl RateCache {
+    pub fn new(api_key: String, ttl: Duration) -> Self {
+        Self {
+            api_key,
+            ttl,
+            entries: RwLock::new(HashMap::new()),
+            store: None,
+            updates: broadcast::channel(UPDATE_CAPACITY).0,
+        }
+    }
+
+    /// Records every fetched rate table in `store`.
+    pub fn with_store(mut self, store: Option<Arc<SnapshotStore>>) -> Self {
+        self.store = store;
+        self
+    }
+
+    /// Returns the rate table for `base`, fetching it when missing or older than the TTL.
+    pub async fn get(&self, base: &str) -> Result<Arc<ExchangeRates>> {
+        let base = base.to_uppercase();
+
+        if let Some(entry) = self.entries.read().await.get(&base)
+            && entry.fetched_at.elapsed() < self.ttl
+        {
+            metrics().record_cache_lookup(true);
+            return Ok(entry.rates.clone());
+        }
+
+        metrics().record_cache_lookup(false);
+        self.refresh(&base).await
+    }
+
+    /// Fetches `base` from the providers regardless of the TTL and caches the result.
+    pub async fn refresh(&self, base: &str) -> Result<Arc<ExchangeRates>> {
+        let base = base.to_uppercase();
+        let rates = Arc::new(crate::fetch_exchange_rates(&self.api_key, &base).await?);
+        crate::record_snapshot(self.store.as_deref(), &rates);
+        self.entries.write().await.insert(
+            base,
+            CachedRates {
+                fetched_at: Instant::now(),
+                rates: rates.clone(),
+            },
+        );
+        // No receivers is the common case and not an error
+        let _ = self.updates.send(rates.clone());
+
+        Ok(rates)
+    }
+
+    /// Fetches `base` from the providers without caching or broadcasting the
+    /// result, so checking on the providers cannot replace the rates being served.
+    pub async fn fetch_uncached(&self, base: &str) -> Result<ExchangeRates> {
+        crate::fetch_exchange_rates(&self.api_key, &base.to_uppercase()).await
+    }
+
+    /// Receives every rate table fetched from now on, for any base.
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<ExchangeRates>> {
+        self.updates.subscribe()
+    }
+
+    /// Caches `rates` under their base as if just fetched.
+    #[cfg(test)]
+    pub async fn insert(&self, rates: ExchangeRates) {
+        let base = rates.base.clone().unwrap_or_default();
+        let rates = Arc::new(rates);
+        self.entries.write().await.insert(
+            base,
+            CachedRates {
+                fetched_at: Instant::now(),
+                rates: rates.clone(),
+            },
+        );
+        let _ = self.updates.send(rates);
+    }
+
+    /// Whether any fresh entry came from a real provider rather than mock data.
+    pub async fn is_warm(&self) -> bool {
+        self.entries.read().await.values().any(|entry| {
+            entry.fetched_at.elapsed() < self.ttl && entry.rates.provider.as_deref() != Some(MOCK_PROVIDER)
+        })
+    }
+
+    /// Publishes the age of each cached table's publication time to the `rate_age_seconds` gauge.
+    /// Only ISO 4217 bases are labelled: requests can cache tables for any
+    /// three-letter base, which would otherwise grow the label set without bound.
+    pub async fn record_rate_ages(&self) {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        for (base, entry) in self.entries.read().await.iter() {
+            if currency::find(base).is_none() {
+                continue;
+            }
+            let published = entry
+                .rates
+                .timestamp
+                .unwrap_or_else(|| now.saturating_sub(entry.fetched_at.elapsed().as_secs()));
+            metrics().rate_age.with_label_values(&[base]).set(now.saturating_sub(published) as f64);
+        }
+    }
+
+    /// Loads entries saved by [`RateCache::save`], keeping their original age.
+    /// Returns the number of rate tables loaded; a missing file loads none.
+    pub async fn load(&self, path: &Path) -> Result<usize> {
+        let contents = match tokio::fs::read(path).await {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(error) => return Err(error).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        let stored: HashMap<String, StoredRates<ExchangeRates>> =
+            serde_json::from_slice(&contents).with_context(|| format!("Failed to parse {}", path.display()))?;
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let mut entries = self.entries.write().await;
+        let count = stored.len();
+        for (base, entry) in stored {
+            let age = Duration::from_secs(now.saturating_sub(entry.fetched_at));
+            // Entries too old to represent as an Instant are simply stale
+            let fetched_at = Instant::now().checked_sub(age).unwrap_or_else(|| Instant::now() - self.ttl);
+            entries.insert(
+                base,
+                CachedRates {
+                    fetched_at,
+                    rates: Arc::new(entry.rates),
+                },
+            );
+        }
+
+        Ok(count)
+    }
+
+    /// Writes every cached rate table to `path`, replacing it atomically.
+    pub async fn save(&self, path: &Path) -> Result<usize> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let entries = self.entries.read().await;
+        let stored: HashMap<&String, StoredRates<&ExchangeRates>> = entries
+            .iter()
+            .map(|(base, entry)| {
+                let stored = StoredRates {
+                    fetched_at: now.saturating_sub(entry.fetched_at.elapsed().as_secs()),
+                    rates: &*entry.rates,
+                };
+                (base, stored)
+            })
+            .collect();
+
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let temporary = path.with_extension("tmp");
+        tokio::fs::write(&temporary, serde_json::to_vec(&stored)?)
+            .await
+            .with_context(|| format!("Failed to write {}", temporary.display()))?;
+        tokio::fs::rename(&temporary, path)
+            .await
+            .with_context(|| format!("Failed to replace {}", path.display()))?;
+
+        Ok(stored.len())
+    }
+}
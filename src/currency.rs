@@ -0,0 +1,182 @@
+//! ISO 4217 currency registry: display names, symbols and minor units.
+
+#[derive(Debug)]
+pub struct Currency {
+    pub code: &'static str,
+    pub name: &'static str,
+    pub symbol: &'static str,
+    /// Number of digits after the decimal separator (2 for cents, 0 for yen).
+    pub minor_units: u8,
+}
+
+const fn currency(code: &'static str, name: &'static str, symbol: &'static str, minor_units: u8) -> Currency {
+    Currency { code, name, symbol, minor_units }
+}
+
+pub static CURRENCIES: &[Currency] = &[
+    currency("AED", "UAE Dirham", "AED", 2),
+    currency("AFN", "Afghan Afghani", "؋", 2),
+    currency("ALL", "Albanian Lek", "L", 2),
+    currency("AMD", "Armenian Dram", "֏", 2),
+    currency("ANG", "Netherlands Antillean Guilder", "ƒ", 2),
+    currency("AOA", "Angolan Kwanza", "Kz", 2),
+    currency("ARS", "Argentine Peso", "ARS", 2),
+    currency("AUD", "Australian Dollar", "A$", 2),
+    currency("AWG", "Aruban Florin", "ƒ", 2),
+    currency("AZN", "Azerbaijani Manat", "₼", 2),
+    currency("BAM", "Bosnia-Herzegovina Convertible Mark", "KM", 2),
+    currency("BBD", "Barbadian Dollar", "Bds$", 2),
+    currency("BDT", "Bangladeshi Taka", "৳", 2),
+    currency("BGN", "Bulgarian Lev", "лв", 2),
+    currency("BHD", "Bahraini Dinar", "BHD", 3),
+    currency("BIF", "Burundian Franc", "FBu", 0),
+    currency("BMD", "Bermudan Dollar", "BD$", 2),
+    currency("BND", "Brunei Dollar", "B$", 2),
+    currency("BOB", "Bolivian Boliviano", "Bs", 2),
+    currency("BRL", "Brazilian Real", "R$", 2),
+    currency("BSD", "Bahamian Dollar", "B$", 2),
+    currency("BTN", "Bhutanese Ngultrum", "Nu.", 2),
+    currency("BWP", "Botswanan Pula", "P", 2),
+    currency("BYN", "Belarusian Ruble", "Br", 2),
+    currency("BZD", "Belize Dollar", "BZ$", 2),
+    currency("CAD", "Canadian Dollar", "CA$", 2),
+    currency("CDF", "Congolese Franc", "FC", 2),
+    currency("CHF", "Swiss Franc", "CHF", 2),
+    currency("CLP", "Chilean Peso", "CLP", 0),
+    currency("CNY", "Chinese Yuan", "CN¥", 2),
+    currency("COP", "Colombian Peso", "COP", 2),
+    currency("CRC", "Costa Rican Colón", "₡", 2),
+    currency("CUP", "Cuban Peso", "CUP", 2),
+    currency("CVE", "Cape Verdean Escudo", "CVE", 2),
+    currency("CZK", "Czech Koruna", "Kč", 2),
+    currency("DJF", "Djiboutian Franc", "Fdj", 0),
+    currency("DKK", "Danish Krone", "kr.", 2),
+    currency("DOP", "Dominican Peso", "RD$", 2),
+    currency("DZD", "Algerian Dinar", "DZD", 2),
+    currency("EGP", "Egyptian Pound", "E£", 2),
+    currency("ERN", "Eritrean Nakfa", "Nfk", 2),
+    currency("ETB", "Ethiopian Birr", "Br", 2),
+    currency("EUR", "Euro", "€", 2),
+    currency("FJD", "Fijian Dollar", "FJ$", 2),
+    currency("FKP", "Falkland Islands Pound", "FK£", 2),
+    currency("GBP", "British Pound", "£", 2),
+    currency("GEL", "Georgian Lari", "₾", 2),
+    currency("GHS", "Ghanaian Cedi", "GH₵", 2),
+    currency("GIP", "Gibraltar Pound", "£", 2),
+    currency("GMD", "Gambian Dalasi", "D", 2),
+    currency("GNF", "Guinean Franc", "FG", 0),
+    currency("GTQ", "Guatemalan Quetzal", "Q", 2),
+    currency("GYD", "Guyanaese Dollar", "G$", 2),
+    currency("HKD", "Hong Kong Dollar", "HK$", 2),
+    currency("HNL", "Honduran Lempira", "L", 2),
+    currency("HTG", "Haitian Gourde", "G", 2),
+    currency("HUF", "Hungarian Forint", "Ft", 2),
+    currency("IDR", "Indonesian Rupiah", "Rp", 2),
+    currency("ILS", "Israeli New Shekel", "₪", 2),
+    currency("INR", "Indian Rupee", "₹", 2),
+    currency("IQD", "Iraqi Dinar", "IQD", 3),
+    currency("IRR", "Iranian Rial", "IRR", 2),
+    currency("ISK", "Icelandic Króna", "kr", 0),
+    currency("JMD", "Jamaican Dollar", "J$", 2),
+    currency("JOD", "Jordanian Dinar", "JOD", 3),
+    currency("JPY", "Japanese Yen", "¥", 0),
+    currency("KES", "Kenyan Shilling", "KSh", 2),
+    currency("KGS", "Kyrgystani Som", "сом", 2),
+    currency("KHR", "Cambodian Riel", "៛", 2),
+    currency("KMF", "Comorian Franc", "CF", 0),
+    currency("KRW", "South Korean Won", "₩", 0),
+    currency("KWD", "Kuwaiti Dinar", "KWD", 3),
+    currency("KYD", "Cayman Islands Dollar", "CI$", 2),
+    currency("KZT", "Kazakhstani Tenge", "₸", 2),
+    currency("LAK", "Laotian Kip", "₭", 2),
+    currency("LBP", "Lebanese Pound", "LBP", 2),
+    currency("LKR", "Sri Lankan Rupee", "Rs", 2),
+    currency("LRD", "Liberian Dollar", "L$", 2),
+    currency("LSL", "Lesotho Loti", "L", 2),
+    currency("LYD", "Libyan Dinar", "LYD", 3),
+    currency("MAD", "Moroccan Dirham", "MAD", 2),
+    currency("MDL", "Moldovan Leu", "L", 2),
+    currency("MGA", "Malagasy Ariary", "Ar", 2),
+    currency("MKD", "Macedonian Denar", "ден", 2),
+    currency("MMK", "Myanmar Kyat", "K", 2),
+    currency("MNT", "Mongolian Tugrik", "₮", 2),
+    currency("MOP", "Macanese Pataca", "MOP$", 2),
+    currency("MRU", "Mauritanian Ouguiya", "UM", 2),
+    currency("MUR", "Mauritian Rupee", "Rs", 2),
+    currency("MVR", "Maldivian Rufiyaa", "Rf", 2),
+    currency("MWK", "Malawian Kwacha", "MK", 2),
+    currency("MXN", "Mexican Peso", "MX$", 2),
+    currency("MYR", "Malaysian Ringgit", "RM", 2),
+    currency("MZN", "Mozambican Metical", "MT", 2),
+    currency("NAD", "Namibian Dollar", "N$", 2),
+    currency("NGN", "Nigerian Naira", "₦", 2),
+    currency("NIO", "Nicaraguan Córdoba", "C$", 2),
+    currency("NOK", "Norwegian Krone", "kr", 2),
+    currency("NPR", "Nepalese Rupee", "Rs", 2),
+    currency("NZD", "New Zealand Dollar", "NZ$", 2),
+    currency("OMR", "Omani Rial", "OMR", 3),
+    currency("PAB", "Panamanian Balboa", "B/.", 2),
+    currency("PEN", "Peruvian Sol", "S/", 2),
+    currency("PGK", "Papua New Guinean Kina", "K", 2),
+    currency("PHP", "Philippine Peso", "₱", 2),
+    currency("PKR", "Pakistani Rupee", "Rs", 2),
+    currency("PLN", "Polish Złoty", "zł", 2),
+    currency("PYG", "Paraguayan Guarani", "₲", 0),
+    currency("QAR", "Qatari Riyal", "QAR", 2),
+    currency("RON", "Romanian Leu", "lei", 2),
+    currency("RSD", "Serbian Dinar", "din.", 2),
+    currency("RUB", "Russian Ruble", "₽", 2),
+    currency("RWF", "Rwandan Franc", "RF", 0),
+    currency("SAR", "Saudi Riyal", "SAR", 2),
+    currency("SBD", "Solomon Islands Dollar", "SI$", 2),
+    currency("SCR", "Seychellois Rupee", "SR", 2),
+    currency("SDG", "Sudanese Pound", "SDG", 2),
+    currency("SEK", "Swedish Krona", "kr", 2),
+    currency("SGD", "Singapore Dollar", "S$", 2),
+    currency("SHP", "St. Helena Pound", "£", 2),
+    currency("SLE", "Sierra Leonean Leone", "Le", 2),
+    currency("SOS", "Somali Shilling", "Sh", 2),
+    currency("SRD", "Surinamese Dollar", "SR$", 2),
+    currency("SSP", "South Sudanese Pound", "SSP", 2),
+    currency("STN", "São Tomé & Príncipe Dobra", "Db", 2),
+    currency("SYP", "Syrian Pound", "SYP", 2),
+    currency("SZL", "Swazi Lilangeni", "E", 2),
+    currency("THB", "Thai Baht", "฿", 2),
+    currency("TJS", "Tajikistani Somoni", "SM", 2),
+    currency("TMT", "Turkmenistani Manat", "m", 2),
+    currency("TND", "Tunisian Dinar", "TND", 3),
+    currency("TOP", "Tongan Paʻanga", "T$", 2),
+    currency("TRY", "Turkish Lira", "₺", 2),
+    currency("TTD", "Trinidad & Tobago Dollar", "TT$", 2),
+    currency("TWD", "New Taiwan Dollar", "NT$", 2),
+    currency("TZS", "Tanzanian Shilling", "TSh", 2),
+    currency("UAH", "Ukrainian Hryvnia", "₴", 2),
+    currency("UGX", "Ugandan Shilling", "USh", 0),
+    currency("USD", "US Dollar", "$", 2),
+    currency("UYU", "Uruguayan Peso", "$U", 2),
+    currency("UZS", "Uzbekistani Som", "soʻm", 2),
+    currency("VES", "Venezuelan Bolívar", "Bs.S", 2),
+    currency("VND", "Vietnamese Dong", "₫", 0),
+    currency("VUV", "Vanuatu Vatu", "VT", 0),
+    currency("WST", "Samoan Tala", "WS$", 2),
+    currency("XAF", "Central African CFA Franc", "FCFA", 0),
+    currency("XCD", "East Caribbean Dollar", "EC$", 2),
+    currency("XOF", "West African CFA Franc", "CFA", 0),
+    currency("XPF", "CFP Franc", "CFPF", 0),
+    currency("YER", "Yemeni Rial", "YER", 2),
+    currency("ZAR", "South African Rand", "R", 2),
+    currency("ZMW", "Zambian Kwacha", "ZK", 2),
+    currency("ZWL", "Zimbabwean Dollar", "ZWL", 2),
+];
+
+/// Looks up a currency by its ISO code (case-insensitive).
+pub fn find(code: &str) -> Option<&'static Currency> {
+    CURRENCIES.iter().find(|currency| currency.code.eq_ignore_ascii_case(code))
+}
+
+/// Whether `code` has the shape of an ISO code: three ASCII letters, in any
+/// case. Codes missing from [`CURRENCIES`] still pass, since providers quote
+/// some (e.g. metals and crypto) that the registry does not list.
+pub fn is_currency_code(code: &str) -> bool {
+    code.len() == 3 && code.chars().all(|c| c.is_ascii_alphabetic())
+}
@@ -0,0 +1,640 @@
+//! Amount expressions shared by the CLI and the web form.
+//!
+//! Besides bare numbers this accepts magnitude suffixes (`1.5k`), numbers
+//! grouped and separated the way the active locale writes them (`1,234.56` in
+//! `en-US`, `1.234,56` or `12,5` in `de-DE`), currency symbols and
+//! codes on either side of an amount (`€20`, `20 EUR`, `15 zł`), arithmetic with
+//! parentheses and percentages (`(120 - 20%) EUR`) and an optional trailing
+//! target (`€20 + £15 to USD`).
+
+use anyhow::{Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+use crate::{ExchangeRates, currency, format::Locale};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Currency(String),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    LParen,
+    RParen,
+    To,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Number(f64),
+    Percent(Box<Expr>),
+    Money(Box<Expr>, String),
+    Neg(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+}
+
+/// A parsed amount expression, optionally carrying its own target currency.
+#[derive(Debug, Clone)]
+pub struct Expression {
+    expr: Expr,
+    pub target: Option<String>,
+}
+
+/// The value of an expression. `currency` is `None` when no term named one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Money {
+    pub amount: f64,
+    pub currency: Option<String>,
+    /// Terms converted into the target to combine them with another
+    /// currency, in evaluation order; empty when no conversion was needed
+    pub legs: Vec<Leg>,
+}
+
+/// One term of a mixed-currency expression and the rate that converted it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Leg {
+    pub currency: String,
+    /// Signed amount in `currency`, negative when subtracted
+    pub amount: f64,
+    /// Units of the target per unit of `currency`
+    pub rate: f64,
+}
+
+enum Value {
+    Scalar(f64),
+    Percent(f64),
+    Money(f64, String),
+}
+
+fn symbol_currency(symbol: &str) -> Option<&'static str> {
+    let code = match symbol {
+        "€" => "EUR",
+        "$" => "USD",
+        "£" => "GBP",
+        "¥" => "JPY",
+        "₴" => "UAH",
+        "₹" => "INR",
+        "₽" => "RUB",
+        "₩" => "KRW",
+        "₺" => "TRY",
+        "₪" => "ILS",
+        "₦" => "NGN",
+        "฿" => "THB",
+        "zł" => "PLN",
+        "Kč" => "CZK",
+        "Ft" => "HUF",
+        "kr" => "SEK",
+        "Fr" => "CHF",
+        _ => return None,
+    };
+    Some(code)
+}
+
+fn is_group_space(c: char) -> bool {
+    matches!(c, ' ' | '\u{a0}' | '\u{202f}')
+}
+
+/// Whether `integer` is digits grouped in threes by `separator`, e.g. `1,234,567`.
+fn is_grouped(integer: &str, separator: char) -> bool {
+    let mut groups = integer.split(separator);
+    groups.next().is_some_and(|first| (1..=3).contains(&first.len())) && groups.all(|group| group.len() == 3)
+}
+
+/// Turns a run of digits and separators into a number using the locale's
+/// `decimal` separator, so `1.234` is 1.234 in `en-US` and 1234 in `de-DE`.
+/// The other of `.` and `,` only groups thousands, and only when every group
+/// after the first has three digits, so `1.5.3` is rejected rather than read
+/// as 153.
+fn parse_number(raw: &str, decimal: char) -> Result<f64> {
+    let group = if decimal == ',' { '.' } else { ',' };
+    let digits: String = raw.chars().filter(|c| !is_group_space(*c) && *c != '\'').collect();
+    let invalid = || anyhow!("Invalid number '{}'", raw);
+
+    let (integer, fraction) = match digits.split_once(decimal) {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (digits.as_str(), None),
+    };
+    if fraction.is_some_and(|fraction| fraction.contains([decimal, group]))
+        || (integer.contains(group) && !is_grouped(integer, group))
+    {
+        return Err(invalid());
+    }
+
+    let integer = integer.replace(group, "");
+    let normalized = match fraction {
+        Some(fraction) => format!("{}.{}", integer, fraction),
+        None => integer,
+    };
+    normalized.parse::<f64>().map_err(|_| invalid())
+}
+
+fn tokenize(input: &str, decimal: char) -> Result<Vec<Token>> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        if c.is_ascii_digit() || (c == decimal && chars.get(i + 1).is_some_and(|n| n.is_ascii_digit())) {
+            let start = i;
+            while i < chars.len() {
+                let c = chars[i];
+                if c.is_ascii_digit() || c == '.' || c == ',' || c == '\'' {
+                    i += 1;
+                } else if is_group_space(c)
+                    && chars.get(i + 1..i + 4).is_some_and(|g| g.iter().all(|d| d.is_ascii_digit()))
+                    && !chars.get(i + 4).is_some_and(|d| d.is_ascii_digit())
+                {
+                    // "1 234,56": a single space followed by a group of exactly three digits
+                    i += 4;
+                } else {
+                    break;
+                }
+            }
+            let raw: String = chars[start..i].iter().collect();
+            let mut value = parse_number(raw.trim_end_matches([',', '.']), decimal)?;
+
+            // Magnitude suffix written directly after the number, e.g. "1.5k"
+            let word_end = (i..chars.len()).find(|&j| !chars[j].is_alphabetic()).unwrap_or(chars.len());
+            let word: String = chars[i..word_end].iter().collect::<String>().to_lowercase();
+            let multiplier = match word.as_str() {
+                "k" => Some(1e3),
+                "m" | "mm" => Some(1e6),
+                "b" | "bn" => Some(1e9),
+                _ => None,
+            };
+            if let Some(multiplier) = multiplier {
+                value *= multiplier;
+                i = word_end;
+            }
+
+            tokens.push(Token::Number(value));
+            continue;
+        }
+
+        if c.is_alphabetic() {
+            let start = i;
+            while i < chars.len() && chars[i].is_alphabetic() {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            let token = match word.to_lowercase().as_str() {
+                "to" | "in" | "into" => Token::To,
+                "x" => Token::Star,
+                _ => {
+                    if let Some(code) = symbol_currency(&word) {
+                        Token::Currency(code.to_string())
+                    } else if currency::is_currency_code(&word) {
+                        Token::Currency(word.to_uppercase())
+                    } else {
+                        bail!("Unknown word '{}' in amount", word);
+                    }
+                }
+            };
+            tokens.push(token);
+            continue;
+        }
+
+        let token = match c {
+            '+' => Token::Plus,
+            '-' | '−' => Token::Minus,
+            '*' | '×' => Token::Star,
+            '/' | '÷' => Token::Slash,
+            '%' => Token::Percent,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '=' if chars.get(i + 1) == Some(&'>') => {
+                i += 1;
+                Token::To
+            }
+            _ => match symbol_currency(&c.to_string()) {
+                Some(code) => Token::Currency(code.to_string()),
+                None => bail!("Unexpected character '{}' in amount", c),
+            },
+        };
+        tokens.push(token);
+        i += 1;
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn sum(&mut self) -> Result<Expr> {
+        let mut left = self.product()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Plus) => Op::Add,
+                Some(Token::Minus) => Op::Sub,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            let right = self.product()?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr> {
+        let mut left = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Star) => Op::Mul,
+                Some(Token::Slash) => Op::Div,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            let right = self.unary()?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        match self.peek() {
+            Some(Token::Minus) => {
+                self.pos += 1;
+                Ok(Expr::Neg(Box::new(self.unary()?)))
+            }
+            Some(Token::Plus) => {
+                self.pos += 1;
+                self.unary()
+            }
+            _ => self.postfix(),
+        }
+    }
+
+    fn postfix(&mut self) -> Result<Expr> {
+        let mut expr = self.primary()?;
+        if self.peek() == Some(&Token::Percent) {
+            self.pos += 1;
+            expr = Expr::Percent(Box::new(expr));
+        }
+        if let Some(Token::Currency(code)) = self.peek().cloned() {
+            self.pos += 1;
+            expr = Expr::Money(Box::new(expr), code);
+        }
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        match self.next() {
+            Some(Token::Number(value)) => Ok(Expr::Number(value)),
+            Some(Token::Currency(code)) => {
+                let amount = self.primary()?;
+                Ok(Expr::Money(Box::new(amount), code))
+            }
+            Some(Token::LParen) => {
+                let inner = self.sum()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(inner),
+                    _ => bail!("Missing closing parenthesis in amount"),
+                }
+            }
+            Some(token) => bail!("Unexpected {:?} in amount", token),
+            None => bail!("Amount is incomplete"),
+        }
+    }
+}
+
+/// Parses an amount expression such as `1.5k`, `€20 + £15 to USD` or `(120 - 20%) EUR`,
+/// reading numbers with `locale`'s decimal separator.
+pub fn parse(input: &str, locale: &Locale) -> Result<Expression> {
+    let tokens = tokenize(input, locale.decimal_separator())?;
+    if tokens.is_empty() {
+        bail!("Amount is empty");
+    }
+
+    let mut parser = Parser { tokens, pos: 0 };
+    let expr = parser.sum()?;
+
+    let target = match parser.next() {
+        None => None,
+        Some(Token::To) => match parser.next() {
+            Some(Token::Currency(code)) => Some(code),
+            _ => bail!("Expected a currency code after 'to'"),
+        },
+        Some(token) => bail!("Unexpected {:?} in amount", token),
+    };
+    if let Some(token) = parser.next() {
+        bail!("Unexpected {:?} after target currency", token);
+    }
+
+    Ok(Expression { expr, target })
+}
+
+/// Rate for converting one unit of `from` into `to`, derived from a table of any base.
+pub fn cross_rate(rates: &ExchangeRates, from: &str, to: &str) -> Result<f64> {
+    let base = rates.base.as_deref().unwrap_or_default();
+    let lookup = |code: &str| -> Result<f64> {
+        if code == base {
+            return Ok(1.0);
+        }
+        rates
+            .rates
+            .get(code)
+            .copied()
+            .ok_or_else(|| anyhow!("Currency {} not found", code))
+    };
+
+    if from == to {
+        return Ok(1.0);
+    }
+    Ok(lookup(to)? / lookup(from)?)
+}
+
+impl Expression {
+    /// Currencies named explicitly in the expression (not including the target).
+    pub fn currencies(&self) -> BTreeSet<String> {
+        fn collect(expr: &Expr, out: &mut BTreeSet<String>) {
+            match expr {
+                Expr::Number(_) => {}
+                Expr::Percent(inner) | Expr::Neg(inner) => collect(inner, out),
+                Expr::Money(inner, code) => {
+                    out.insert(code.clone());
+                    collect(inner, out);
+                }
+                Expr::Binary(_, left, right) => {
+                    collect(left, out);
+                    collect(right, out);
+                }
+            }
+        }
+
+        let mut out = BTreeSet::new();
+        collect(&self.expr, &mut out);
+        out
+    }
+
+    /// Evaluates the expression. Terms in different currencies are converted into
+    /// `target` using `rates` before they are combined.
+    pub fn evaluate(&self, target: &str, rates: &ExchangeRates) -> Result<Money> {
+        let mut legs = Vec::new();
+        let value = eval(&self.expr, target, rates, &mut legs)?;
+        let (amount, currency) = match value {
+            Value::Scalar(amount) => (amount, None),
+            Value::Percent(percent) => (percent / 100.0, None),
+            Value::Money(amount, code) => (amount, Some(code)),
+        };
+        let money = Money { amount, currency, legs };
+        if !money.amount.is_finite() {
+            bail!("Amount is not a finite number");
+        }
+        Ok(money)
+    }
+}
+
+fn eval(expr: &Expr, target: &str, rates: &ExchangeRates, legs: &mut Vec<Leg>) -> Result<Value> {
+    let value = match expr {
+        Expr::Number(value) => Value::Scalar(*value),
+        Expr::Percent(inner) => match eval(inner, target, rates, legs)? {
+            Value::Scalar(value) => Value::Percent(value),
+            _ => bail!("Only plain numbers can be percentages"),
+        },
+        Expr::Neg(inner) => match eval(inner, target, rates, legs)? {
+            Value::Scalar(value) => Value::Scalar(-value),
+            Value::Percent(value) => Value::Percent(-value),
+            Value::Money(value, code) => Value::Money(-value, code),
+        },
+        Expr::Money(inner, code) => match eval(inner, target, rates, legs)? {
+            Value::Scalar(value) => Value::Money(value, code.clone()),
+            Value::Money(value, existing) if existing == *code => Value::Money(value, existing),
+            Value::Money(_, existing) => bail!("Amount in {} cannot also be in {}", existing, code),
+            Value::Percent(_) => bail!("A percentage cannot have a currency"),
+        },
+        Expr::Binary(op, left, right) => {
+            let left = eval(left, target, rates, legs)?;
+            let right = eval(right, target, rates, legs)?;
+            let mut to_target = |amount: f64, code: &str| -> Result<f64> {
+                let rate = cross_rate(rates, code, target)?;
+                legs.push(Leg { currency: code.to_string(), amount, rate });
+                Ok(amount * rate)
+            };
+            match (op, left, right) {
+                (Op::Add | Op::Sub, Value::Percent(_), _) => bail!("A percentage must follow the amount it applies to"),
+                (Op::Add, Value::Scalar(a), Value::Percent(p)) => Value::Scalar(a + a * p / 100.0),
+                (Op::Sub, Value::Scalar(a), Value::Percent(p)) => Value::Scalar(a - a * p / 100.0),
+                (Op::Add, Value::Money(a, c), Value::Percent(p)) => Value::Money(a + a * p / 100.0, c),
+                (Op::Sub, Value::Money(a, c), Value::Percent(p)) => Value::Money(a - a * p / 100.0, c),
+                (Op::Add | Op::Sub, a, b) => {
+                    let sign = if matches!(op, Op::Sub) { -1.0 } else { 1.0 };
+                    match (a, b) {
+                        (Value::Scalar(a), Value::Scalar(b)) => Value::Scalar(a + sign * b),
+                        (Value::Money(a, c), Value::Scalar(b)) => Value::Money(a + sign * b, c),
+                        (Value::Scalar(a), Value::Money(b, c)) => Value::Money(a + sign * b, c),
+                        (Value::Money(a, ca), Value::Money(b, cb)) if ca == cb => Value::Money(a + sign * b, ca),
+                        (Value::Money(a, ca), Value::Money(b, cb)) => {
+                            Value::Money(to_target(a, &ca)? + to_target(sign * b, &cb)?, target.to_string())
+                        }
+                        _ => unreachable!("percentages are handled above"),
+                    }
+                }
+                (Op::Mul, Value::Money(..), Value::Money(..)) => bail!("Cannot multiply two amounts of money"),
+                (Op::Mul, Value::Money(a, c), Value::Scalar(b)) | (Op::Mul, Value::Scalar(b), Value::Money(a, c)) => {
+                    Value::Money(a * b, c)
+                }
+                (Op::Mul, Value::Money(a, c), Value::Percent(p)) | (Op::Mul, Value::Percent(p), Value::Money(a, c)) => {
+                    Value::Money(a * p / 100.0, c)
+                }
+                (Op::Mul, Value::Scalar(a), Value::Scalar(b)) => Value::Scalar(a * b),
+                (Op::Mul, Value::Scalar(a), Value::Percent(p)) | (Op::Mul, Value::Percent(p), Value::Scalar(a)) => {
+                    Value::Scalar(a * p / 100.0)
+                }
+                (Op::Mul, Value::Percent(a), Value::Percent(b)) => Value::Percent(a * b / 100.0),
+                (Op::Div, _, Value::Scalar(b) | Value::Percent(b)) if b == 0.0 => bail!("Division by zero"),
+                (Op::Div, Value::Money(a, c), Value::Scalar(b)) => Value::Money(a / b, c),
+                (Op::Div, Value::Money(a, c), Value::Percent(p)) => Value::Money(a / (p / 100.0), c),
+                (Op::Div, Value::Money(a, ca), Value::Money(b, cb)) => {
+                    let denominator = to_target(b, &cb)?;
+                    if denominator == 0.0 {
+                        bail!("Division by zero");
+                    }
+                    Value::Scalar(to_target(a, &ca)? / denominator)
+                }
+                (Op::Div, Value::Scalar(_) | Value::Percent(_), Value::Money(..)) => {
+                    bail!("Cannot divide a number by an amount of money")
+                }
+                (Op::Div, Value::Scalar(a), Value::Scalar(b)) => Value::Scalar(a / b),
+                (Op::Div, Value::Scalar(a), Value::Percent(p)) => Value::Scalar(a / (p / 100.0)),
+                (Op::Div, Value::Percent(a), Value::Scalar(b)) => Value::Percent(a / b),
+                (Op::Div, Value::Percent(a), Value::Percent(b)) => Value::Scalar(a / b),
+            }
+        }
+    };
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn rates() -> ExchangeRates {
+        ExchangeRates {
+            success: true,
+            timestamp: None,
+            base: Some("EUR".to_string()),
+            date: None,
+            rates: HashMap::from([("USD".to_string(), 1.1), ("GBP".to_string(), 0.8), ("PLN".to_string(), 4.0)]),
+            provider: Some("api.frankfurter.app".to_string()),
+            fetched_at: None,
+        }
+    }
+
+    fn en() -> Locale {
+        Locale::parse("en-US").unwrap()
+    }
+
+    fn de() -> Locale {
+        Locale::parse("de-DE").unwrap()
+    }
+
+    fn value(input: &str) -> Money {
+        value_in(input, &en())
+    }
+
+    fn value_in(input: &str, locale: &Locale) -> Money {
+        let expression = parse(input, locale).unwrap();
+        let target = expression.target.clone().unwrap_or_else(|| "EUR".to_string());
+        expression.evaluate(&target, &rates()).unwrap()
+    }
+
+    fn amount(input: &str) -> f64 {
+        value(input).amount
+    }
+
+    fn error(input: &str) -> String {
+        match parse(input, &en()) {
+            Ok(expression) => expression.evaluate("EUR", &rates()).unwrap_err().to_string(),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn numbers_follow_the_locale_separators() {
+        assert_eq!(parse_number("1,234.5", '.').unwrap(), 1234.5);
+        assert_eq!(parse_number("1.234,5", ',').unwrap(), 1234.5);
+        assert!(parse_number("1.234,5", '.').is_err());
+        assert!(parse_number("1,234.5", ',').is_err());
+
+        // The same text means different amounts in different locales
+        assert_eq!(parse_number("1.234", '.').unwrap(), 1.234);
+        assert_eq!(parse_number("1.234", ',').unwrap(), 1234.0);
+        assert_eq!(parse_number("1,234", '.').unwrap(), 1234.0);
+        assert_eq!(parse_number("1,234", ',').unwrap(), 1.234);
+
+        assert_eq!(parse_number("1 234,56", ',').unwrap(), 1234.56);
+        assert_eq!(parse_number("1'234.5", '.').unwrap(), 1234.5);
+        assert_eq!(parse_number("1,234,567", '.').unwrap(), 1_234_567.0);
+        assert_eq!(parse_number("1.234.567", ',').unwrap(), 1_234_567.0);
+        assert_eq!(parse_number("12,50", ',').unwrap(), 12.5);
+
+        for (malformed, decimal) in [("1.5.3", '.'), ("1,2,3", '.'), ("1,5", '.'), ("1.23,45", ','), ("1,5,3", ',')] {
+            assert!(parse_number(malformed, decimal).is_err(), "{} should be rejected", malformed);
+        }
+    }
+
+    #[test]
+    fn expressions_are_read_in_the_active_locale() {
+        assert_eq!(value_in("1.234,5", &de()).amount, 1234.5);
+        assert_eq!(value_in("1,234.5", &en()).amount, 1234.5);
+        assert!(parse("1.234,5", &en()).is_err());
+        assert!(parse("1,234.5", &de()).is_err());
+        assert_eq!(value_in(",5 + 1", &de()).amount, 1.5);
+    }
+
+    #[test]
+    fn amounts_take_suffixes_symbols_and_codes() {
+        assert_eq!(amount("1.5k"), 1500.0);
+        assert_eq!(amount("2mm"), 2_000_000.0);
+        assert_eq!(value("€20"), Money { amount: 20.0, currency: Some("EUR".to_string()), legs: Vec::new() });
+        assert_eq!(value("20 usd"), Money { amount: 20.0, currency: Some("USD".to_string()), legs: Vec::new() });
+        assert_eq!(value_in("1 234,56 zł", &de()), Money { amount: 1234.56, currency: Some("PLN".to_string()), legs: Vec::new() });
+        assert_eq!(value("1,234.56"), Money { amount: 1234.56, currency: None, legs: Vec::new() });
+    }
+
+    #[test]
+    fn operators_follow_arithmetic_precedence() {
+        assert_eq!(amount("2 + 3 * 4"), 14.0);
+        assert_eq!(amount("(2 + 3) * 4"), 20.0);
+        assert_eq!(amount("10 - 4 - 3"), 3.0);
+        assert_eq!(amount("-(2 + 3) x 2"), -10.0);
+        assert_eq!(amount("12 / 4 / 3"), 1.0);
+    }
+
+    #[test]
+    fn percentages_apply_to_the_preceding_amount() {
+        assert_eq!(value("(120 - 20%) EUR"), Money { amount: 96.0, currency: Some("EUR".to_string()), legs: Vec::new() });
+        assert_eq!(amount("200 + 10%"), 220.0);
+        assert_eq!(amount("50% * 200"), 100.0);
+        assert_eq!(amount("€80 / 50%"), 160.0);
+        assert_eq!(amount("25%"), 0.25);
+    }
+
+    #[test]
+    fn mixed_currencies_are_converted_into_the_target() {
+        let expression = parse("€20 + £16 to USD", &en()).unwrap();
+        assert_eq!(expression.target.as_deref(), Some("USD"));
+        assert_eq!(expression.currencies(), BTreeSet::from(["EUR".to_string(), "GBP".to_string()]));
+
+        let money = expression.evaluate("USD", &rates()).unwrap();
+        assert_eq!(money.currency.as_deref(), Some("USD"));
+        // 20 EUR = 22 USD, 16 GBP = 20 EUR = 22 USD
+        assert!((money.amount - 44.0).abs() < 1e-9);
+        let legs: Vec<(&str, f64)> = money.legs.iter().map(|leg| (leg.currency.as_str(), leg.amount)).collect();
+        assert_eq!(legs, [("EUR", 20.0), ("GBP", 16.0)]);
+        assert!((money.legs[1].rate - 1.375).abs() < 1e-9);
+
+        let money = parse("€20 - £16 to USD", &en()).unwrap().evaluate("USD", &rates()).unwrap();
+        assert_eq!(money.legs[1].amount, -16.0);
+        assert!(parse("€20 + 5 to USD", &en()).unwrap().evaluate("USD", &rates()).unwrap().legs.is_empty());
+    }
+
+    #[test]
+    fn malformed_amounts_are_rejected() {
+        assert_eq!(error(""), "Amount is empty");
+        assert_eq!(error("1.5.3"), "Invalid number '1.5.3'");
+        assert_eq!(error("(1 + 2"), "Missing closing parenthesis in amount");
+        assert_eq!(error("5 +"), "Amount is incomplete");
+        assert_eq!(error("10 to"), "Expected a currency code after 'to'");
+        assert_eq!(error("10 dollars"), "Unknown word 'dollars' in amount");
+        assert_eq!(error("10 # 2"), "Unexpected character '#' in amount");
+        assert_eq!(error("20% EUR"), "A percentage cannot have a currency");
+        assert_eq!(error("10% + 5"), "A percentage must follow the amount it applies to");
+        assert_eq!(error("€5 * £5"), "Cannot multiply two amounts of money");
+        assert_eq!(error("1 / 0"), "Division by zero");
+        assert_eq!(error("€5 GBP"), "Amount in EUR cannot also be in GBP");
+        assert_eq!(error("10 XYZ + 5 EUR"), "Currency XYZ not found");
+    }
+}
@@ -0,0 +1,224 @@
+//! Number formatting helpers shared by the CLI and the web templates.
+
+/// Significant figures used when displaying exchange rates.
+pub const RATE_SIGNIFICANT_FIGURES: usize = 6;
+
+/// Formats `value` with `figures` significant figures, dropping trailing zeros.
+///
+/// Unlike a fixed number of decimals this keeps small rates readable, so
+/// 1 UAH shows as `0.0235294` EUR rather than `0.02`.
+pub fn significant(value: f64, figures: usize) -> String {
+    if value == 0.0 || !value.is_finite() {
+        return format!("{}", value);
+    }
+
+    let magnitude = value.abs().log10().floor() as i32;
+    let decimals = (figures as i32 - 1 - magnitude).max(0) as usize;
+    let formatted = format!("{:.*}", decimals, value);
+
+    if formatted.contains('.') {
+        formatted.trim_end_matches('0').trim_end_matches('.').to_string()
+    } else {
+        formatted
+    }
+}
+
+/// Separator and symbol placement rules for rendering money in one locale.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Locale {
+    pub tag: &'static str,
+    group: &'static str,
+    decimal: &'static str,
+    symbol_first: bool,
+    symbol_space: bool,
+}
+
+const fn locale(tag: &'static str, group: &'static str, decimal: &'static str, symbol_first: bool, symbol_space: bool) -> Locale {
+    Locale { tag, group, decimal, symbol_first, symbol_space }
+}
+
+pub static LOCALES: &[Locale] = &[
+    locale("en-US", ",", ".", true, false),
+    locale("en-GB", ",", ".", true, false),
+    locale("de-DE", ".", ",", false, true),
+    locale("de-AT", "\u{a0}", ",", true, true),
+    locale("de-CH", "’", ".", true, true),
+    locale("fr-FR", "\u{202f}", ",", false, true),
+    locale("fr-CH", "\u{202f}", ",", false, true),
+    locale("es-ES", ".", ",", false, true),
+    locale("it-IT", ".", ",", false, true),
+    locale("nl-NL", ".", ",", true, true),
+    locale("pt-BR", ".", ",", true, true),
+    locale("pt-PT", "\u{a0}", ",", false, true),
+    locale("pl-PL", "\u{a0}", ",", false, true),
+    locale("uk-UA", "\u{a0}", ",", false, true),
+    locale("cs-CZ", "\u{a0}", ",", false, true),
+    locale("sv-SE", "\u{a0}", ",", false, true),
+    locale("ru-RU", "\u{a0}", ",", false, true),
+    locale("ja-JP", ",", ".", true, false),
+    locale("zh-CN", ",", ".", true, false),
+    locale("ko-KR", ",", ".", true, false),
+];
+
+impl Default for Locale {
+    fn default() -> Self {
+        LOCALES[0]
+    }
+}
+
+impl Locale {
+    /// The decimal separator, `.` or `,`.
+    pub fn decimal_separator(&self) -> char {
+        self.decimal.chars().next().unwrap_or('.')
+    }
+
+    /// Resolves a tag such as `pl-PL`, `pl_PL.UTF-8` or `pl` to a supported locale.
+    pub fn parse(tag: &str) -> Option<Locale> {
+        let tag = tag.split('.').next().unwrap_or_default().replace('_', "-");
+        let language = tag.split('-').next().unwrap_or_default();
+
+        LOCALES
+            .iter()
+            .find(|locale| locale.tag.eq_ignore_ascii_case(&tag))
+            .or_else(|| {
+                LOCALES
+                    .iter()
+                    .find(|locale| locale.tag.split('-').next().is_some_and(|l| l.eq_ignore_ascii_case(language)))
+            })
+            .copied()
+    }
+
+    /// Picks the most preferred supported locale from an `Accept-Language` header.
+    pub fn from_accept_language(header: &str) -> Option<Locale> {
+        let mut candidates: Vec<(f32, &str)> = header
+            .split(',')
+            .filter_map(|entry| {
+                let mut parts = entry.split(';');
+                let tag = parts.next()?.trim();
+                let quality = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .and_then(|q| q.parse().ok())
+                    .unwrap_or(1.0);
+                // q=0 marks a language as not acceptable
+                (!tag.is_empty() && tag != "*" && quality > 0.0).then_some((quality, tag))
+            })
+            .collect();
+        candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        candidates.into_iter().find_map(|(_, tag)| Locale::parse(tag))
+    }
+}
+
+/// Decimal places amounts in `code` are shown with (2 for unknown currencies).
+pub fn minor_units(code: &str) -> usize {
+    crate::currency::find(code).map_or(2, |currency| currency.minor_units as usize)
+}
+
+/// How [`money`] rounds to minor units: Rust's formatting rounds the exact
+/// binary value, with ties going to the even digit.
+pub const ROUNDING_MODE: &str = "half-even";
+
+/// `amount` rounded to the currency's minor units, without symbol or grouping.
+pub fn rounded(amount: f64, code: &str) -> String {
+    format!("{:.*}", minor_units(code), amount)
+}
+
+/// [`money`] with a `+` on gains, for changes in value.
+pub fn signed_money(amount: f64, code: &str, locale: &Locale) -> String {
+    let formatted = money(amount, code, locale);
+    let zero = rounded(amount.abs(), code).chars().all(|c| c == '0' || c == '.');
+    if amount > 0.0 && !zero { format!("+{}", formatted) } else { formatted }
+}
+
+/// Formats `amount` of `code` for `locale`, using the currency's symbol and minor units.
+pub fn money(amount: f64, code: &str, locale: &Locale) -> String {
+    let symbol = crate::currency::find(code).map_or(code, |currency| currency.symbol);
+
+    let fixed = format!("{:.*}", minor_units(code), amount.abs());
+    let (integer, fraction) = fixed.split_once('.').unwrap_or((&fixed, ""));
+
+    let mut number = String::new();
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            number.push_str(locale.group);
+        }
+        number.push(digit);
+    }
+    if !fraction.is_empty() {
+        number.push_str(locale.decimal);
+        number.push_str(fraction);
+    }
+
+    let negative = amount < 0.0 && fixed.chars().any(|c| c.is_ascii_digit() && c != '0');
+    let sign = if negative { "-" } else { "" };
+    // Alphabetic symbols such as "CHF" are always kept apart from the digits
+    let space = if locale.symbol_space || symbol.ends_with(|c: char| c.is_alphabetic()) { " " } else { "" };
+
+    if locale.symbol_first {
+        format!("{}{}{}{}", sign, symbol, space, number)
+    } else {
+        format!("{}{} {}", sign, number, symbol)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rates_keep_significant_figures() {
+        assert_eq!(significant(1.0 / 42.5, RATE_SIGNIFICANT_FIGURES), "0.0235294");
+        assert_eq!(significant(0.000012345678, RATE_SIGNIFICANT_FIGURES), "0.0000123457");
+        assert_eq!(significant(0.925, RATE_SIGNIFICANT_FIGURES), "0.925");
+        assert_eq!(significant(160.123456, RATE_SIGNIFICANT_FIGURES), "160.123");
+        assert_eq!(significant(1_234_567.89, RATE_SIGNIFICANT_FIGURES), "1234568");
+        assert_eq!(significant(42.0, RATE_SIGNIFICANT_FIGURES), "42");
+        assert_eq!(significant(1.0, RATE_SIGNIFICANT_FIGURES), "1");
+        assert_eq!(significant(-0.5, RATE_SIGNIFICANT_FIGURES), "-0.5");
+        assert_eq!(significant(0.0, RATE_SIGNIFICANT_FIGURES), "0");
+    }
+
+    fn locale(tag: &str) -> Locale {
+        Locale::parse(tag).unwrap()
+    }
+
+    #[test]
+    fn money_follows_the_locale_and_currency() {
+        assert_eq!(money(1234.56, "USD", &locale("en-US")), "$1,234.56");
+        assert_eq!(money(1234.56, "PLN", &locale("pl-PL")), "1\u{a0}234,56 zł");
+        assert_eq!(money(1234.56, "JPY", &locale("en-US")), "¥1,235");
+        assert_eq!(money(1234.5, "EUR", &locale("de-DE")), "1.234,50 €");
+        assert_eq!(money(-1234.56, "USD", &locale("en-US")), "-$1,234.56");
+        assert_eq!(money(-0.001, "USD", &locale("en-US")), "$0.00");
+        assert_eq!(money(12.5, "CHF", &locale("en-US")), "CHF 12.50");
+        assert_eq!(money(1.234, "KWD", &locale("en-US")), "KWD 1.234");
+    }
+
+    #[test]
+    fn locales_resolve_from_tags_and_accept_language() {
+        assert_eq!(locale("pl_PL.UTF-8").tag, "pl-PL");
+        assert_eq!(locale("de").tag, "de-DE");
+        assert_eq!(locale("FR-ch").tag, "fr-CH");
+        assert!(Locale::parse("xx-YY").is_none());
+
+        let accept = |header: &str| Locale::from_accept_language(header).map(|locale| locale.tag);
+        assert_eq!(accept("pl-PL,pl;q=0.9,en;q=0.8"), Some("pl-PL"));
+        assert_eq!(accept("en;q=0.5, de-DE;q=0.9, *;q=0.1"), Some("de-DE"));
+        assert_eq!(accept("xx, fr-FR;q=0.7"), Some("fr-FR"));
+        assert_eq!(accept("pl;q=0, en-GB;q=0.3"), Some("en-GB"));
+        assert_eq!(accept("*"), None);
+    }
+
+    #[test]
+    fn rounding_is_half_even_on_the_exact_value() {
+        assert_eq!(ROUNDING_MODE, "half-even");
+        // Exactly representable ties go to the even digit
+        assert_eq!(rounded(0.125, "USD"), "0.12");
+        assert_eq!(rounded(0.375, "USD"), "0.38");
+        assert_eq!(rounded(2.5, "JPY"), "2");
+        assert_eq!(rounded(3.5, "JPY"), "4");
+        // 1.005 is stored just below the tie, so it rounds down
+        assert_eq!(rounded(1.005, "USD"), "1.00");
+        assert_eq!(rounded(1.2345, "KWD"), "1.234");
+    }
+}
@@ -0,0 +1,109 @@
+//! Process-wide Prometheus metrics.
+//!
+//! Counters and histograms are recorded where the work happens (HTTP layer,
+//! provider fetches, rate cache) and rendered in the text exposition format
+//! by the web server's `/metrics` endpoint.
+
+use prometheus::{
+    Encoder, Gauge, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+pub struct Metrics {
+    registry: Registry,
+    pub http_requests: IntCounterVec,
+    pub http_request_duration: HistogramVec,
+    pub provider_fetches: IntCounterVec,
+    pub provider_fetch_duration: HistogramVec,
+    pub cache_lookups: IntCounterVec,
+    pub cache_hit_ratio: Gauge,
+    pub rate_age: GaugeVec,
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(|| Metrics::new().expect("metric definitions are valid"));
+
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+impl Metrics {
+    fn new() -> prometheus::Result<Self> {
+        let registry = Registry::new_custom(Some("currency_converter".to_string()), None)?;
+
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests handled, by route and status"),
+            &["method", "route", "status"],
+        )?;
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "HTTP request latency, by route"),
+            &["method", "route"],
+        )?;
+        let provider_fetches = IntCounterVec::new(
+            Opts::new("provider_fetches_total", "Rate provider fetch attempts, by outcome"),
+            &["provider", "outcome"],
+        )?;
+        let provider_fetch_duration = HistogramVec::new(
+            HistogramOpts::new("provider_fetch_duration_seconds", "Rate provider fetch latency")
+                .buckets(vec![0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0]),
+            &["provider"],
+        )?;
+        let cache_lookups = IntCounterVec::new(
+            Opts::new("rate_cache_lookups_total", "Rate cache lookups, by hit or miss"),
+            &["result"],
+        )?;
+        let cache_hit_ratio = Gauge::new("rate_cache_hit_ratio", "Share of rate cache lookups served from the cache")?;
+        let rate_age = GaugeVec::new(
+            Opts::new("rate_age_seconds", "Age of the cached rate table's publication time, by base"),
+            &["base"],
+        )?;
+
+        registry.register(Box::new(http_requests.clone()))?;
+        registry.register(Box::new(http_request_duration.clone()))?;
+        registry.register(Box::new(provider_fetches.clone()))?;
+        registry.register(Box::new(provider_fetch_duration.clone()))?;
+        registry.register(Box::new(cache_lookups.clone()))?;
+        registry.register(Box::new(cache_hit_ratio.clone()))?;
+        registry.register(Box::new(rate_age.clone()))?;
+
+        Ok(Self {
+            registry,
+            http_requests,
+            http_request_duration,
+            provider_fetches,
+            provider_fetch_duration,
+            cache_lookups,
+            cache_hit_ratio,
+            rate_age,
+        })
+    }
+
+    pub fn record_provider_fetch(&self, provider: &str, success: bool, elapsed: Duration) {
+        let outcome = if success { "success" } else { "error" };
+        self.provider_fetches.with_label_values(&[provider, outcome]).inc();
+        self.provider_fetch_duration
+            .with_label_values(&[provider])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn record_cache_lookup(&self, hit: bool) {
+        self.cache_lookups
+            .with_label_values(&[if hit { "hit" } else { "miss" }])
+            .inc();
+    }
+
+    /// Renders all metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let hits = self.cache_lookups.with_label_values(&["hit"]).get();
+        let misses = self.cache_lookups.with_label_values(&["miss"]).get();
+        if hits + misses > 0 {
+            self.cache_hit_ratio.set(hits as f64 / (hits + misses) as f64);
+        }
+
+        let mut buffer = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            tracing::error!("Failed to encode metrics: {}", e);
+        }
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
@@ -0,0 +1,181 @@
+//! Checks on outbound HTTP destinations chosen by API users or operators:
+//! webhook subscriptions and the alert webhook.
+//!
+//! A destination is resolved before each request and refused unless every
+//! address is public, so the server cannot be pointed at its own network
+//! (loopback, private ranges, link-local cloud metadata at 169.254.169.254).
+//! The request then connects only to the checked addresses, and redirects are
+//! not followed, so neither a DNS change nor the destination can steer it
+//! elsewhere afterwards.
+
+use anyhow::{Context, Result, bail};
+use reqwest::{Url, redirect};
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+/// Refusal of a destination that resolves to a non-public address. Unlike
+/// lookup failures, this will not change by retrying.
+#[derive(Debug)]
+pub struct Refused(String);
+
+impl std::fmt::Display for Refused {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Refused {}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || a == 0
+        // Carrier-grade NAT, 100.64.0.0/10
+        || (a == 100 && (64..128).contains(&b))
+        // IETF protocol assignments, 192.0.0.0/24
+        || ip.octets()[..3] == [192, 0, 0]
+        // Benchmarking, 198.18.0.0/15
+        || (a == 198 && (b == 18 || b == 19))
+        // Reserved, 240.0.0.0/4
+        || a >= 240)
+}
+
+/// Whether `ip` is a globally routable unicast address.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            // IPv4-mapped and NAT64 (64:ff9b::/96) addresses reach the embedded IPv4 address
+            if let Some(mapped) = ip.to_ipv4_mapped() {
+                return is_public_v4(mapped);
+            }
+            if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+                let [.., a, b, c, d] = ip.octets();
+                return is_public_v4(Ipv4Addr::new(a, b, c, d));
+            }
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_multicast()
+                || ip.is_unique_local()
+                || ip.is_unicast_link_local()
+                // Documentation, 2001:db8::/32
+                || segments[..2] == [0x2001, 0xdb8]
+                // Deprecated IPv4-compatible addresses, ::/96
+                || segments[..6] == [0; 6])
+        }
+    }
+}
+
+/// The address of an IP literal host, e.g. `127.0.0.1` or `[::1]`.
+fn literal_ip(host: &str) -> Option<IpAddr> {
+    host.trim_start_matches('[').trim_end_matches(']').parse().ok()
+}
+
+/// Parses `url`, which must be http or https with a host.
+pub fn parse(url: &str) -> Result<Url> {
+    let url = Url::parse(url.trim()).context("Invalid URL")?;
+    if !matches!(url.scheme(), "http" | "https") {
+        bail!("URL must use http or https");
+    }
+    if url.host_str().is_none_or(str::is_empty) {
+        bail!("URL must have a host");
+    }
+    Ok(url)
+}
+
+/// Resolves `url`'s host, failing with [`Refused`] unless every address is
+/// public or `allow_private` is set.
+pub async fn resolve(url: &Url, allow_private: bool, timeout: Duration) -> Result<Vec<SocketAddr>> {
+    let host = url.host_str().context("URL must have a host")?;
+    let port = url.port_or_known_default().context("URL has no port")?;
+    let addrs: Vec<SocketAddr> = match literal_ip(host) {
+        Some(ip) => vec![SocketAddr::new(ip, port)],
+        None => tokio::time::timeout(timeout, tokio::net::lookup_host((host, port)))
+            .await
+            .with_context(|| format!("Timed out resolving {}", host))?
+            .with_context(|| format!("Failed to resolve {}", host))?
+            .collect(),
+    };
+    if addrs.is_empty() {
+        bail!("{} has no addresses", host);
+    }
+
+    if !allow_private && let Some(addr) = addrs.iter().find(|addr| !is_public(addr.ip())) {
+        let refusal = match literal_ip(host) {
+            Some(ip) => format!("{} is not a public address", ip),
+            None => format!("{} resolves to {}, which is not a public address", host, addr.ip()),
+        };
+        return Err(Refused(refusal).into());
+    }
+    Ok(addrs)
+}
+
+/// Client for requests to `url`, connecting only to the addresses checked by
+/// [`resolve`]. Redirects are not followed and proxies are not used.
+pub async fn client(url: &Url, allow_private: bool, timeout: Duration) -> Result<reqwest::Client> {
+    let addrs = resolve(url, allow_private, timeout).await?;
+    let mut builder = reqwest::Client::builder()
+        .timeout(timeout)
+        .redirect(redirect::Policy::none())
+        .no_proxy();
+    if let Some(host) = url.host_str().filter(|host| literal_ip(host).is_none()) {
+        builder = builder.resolve_to_addrs(host, &addrs);
+    }
+    Ok(builder.build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_public_addresses_are_public() {
+        for ip in ["8.8.8.8", "1.1.1.1", "2606:4700:4700::1111", "::ffff:8.8.8.8"] {
+            assert!(is_public(ip.parse().unwrap()), "{} should be public", ip);
+        }
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "224.0.0.1",
+            "::1",
+            "::",
+            "fe80::1",
+            "fd00:ec2::254",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+            "::127.0.0.1",
+            "64:ff9b::a9fe:a9fe",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{} should not be public", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn private_destinations_are_refused_unless_allowed() {
+        let timeout = Duration::from_secs(5);
+        for url in ["http://169.254.169.254/latest/meta-data/", "http://[::1]:8080/hook", "https://localhost/hook"] {
+            let error = resolve(&parse(url).unwrap(), false, timeout).await.unwrap_err();
+            assert!(error.is::<Refused>(), "{}: {:#}", url, error);
+        }
+        let addrs = resolve(&parse("http://127.0.0.1:9/hook").unwrap(), true, timeout).await.unwrap();
+        assert_eq!(addrs, ["127.0.0.1:9".parse().unwrap()]);
+
+        assert!(parse("ftp://example.com/").is_err());
+        assert!(parse("not a url").is_err());
+    }
+}
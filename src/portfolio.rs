@@ -0,0 +1,261 @@
+//! Valuation of multi-currency holdings in a single reporting currency.
+//!
+//! Holdings come from a TOML or CSV file (or the web form, as CSV). Every
+//! holding is valued from the same rate table, so the total reflects one
+//! consistent snapshot; valuing the same holdings at an earlier table gives
+//! the gain or loss due to exchange-rate moves alone.
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::{ExchangeRates, currency, expr};
+
+/// Most holdings accepted in one portfolio.
+pub const MAX_HOLDINGS: usize = 200;
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Holding {
+    pub currency: String,
+    pub amount: f64,
+    /// Free-form name, e.g. the account holding the balance
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PortfolioFile {
+    holdings: Vec<Holding>,
+}
+
+/// Reads holdings from a `.toml` file (`[[holdings]]` tables) or a `.csv`
+/// file (`currency,amount,label` rows).
+pub fn load(path: &Path) -> Result<Vec<Holding>> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let holdings = match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) if extension.eq_ignore_ascii_case("toml") => from_toml(&text),
+        Some(extension) if extension.eq_ignore_ascii_case("csv") => from_csv(&text),
+        _ => bail!("Unsupported portfolio file {}: expected .toml or .csv", path.display()),
+    };
+    holdings.with_context(|| format!("Invalid portfolio file {}", path.display()))
+}
+
+pub fn from_toml(text: &str) -> Result<Vec<Holding>> {
+    let file: PortfolioFile = toml::from_str(text)?;
+    validate(file.holdings)
+}
+
+/// Parses `currency,amount[,label]` rows; a leading `currency,...` header
+/// row and blank lines are skipped.
+pub fn from_csv(text: &str) -> Result<Vec<Holding>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(text.as_bytes());
+
+    let mut holdings = Vec::new();
+    for (index, record) in reader.records().enumerate() {
+        let record = record?;
+        let line = index + 1;
+        let currency = record.get(0).unwrap_or_default();
+        if currency.is_empty() || (index == 0 && currency.eq_ignore_ascii_case("currency")) {
+            continue;
+        }
+        let amount = record.get(1).with_context(|| format!("Line {}: missing amount", line))?;
+        let amount = amount
+            .replace('_', "")
+            .parse()
+            .with_context(|| format!("Line {}: invalid amount {}", line, amount))?;
+        holdings.push(Holding {
+            currency: currency.to_string(),
+            amount,
+            label: record.get(2).filter(|label| !label.is_empty()).map(str::to_string),
+        });
+    }
+    validate(holdings)
+}
+
+fn validate(mut holdings: Vec<Holding>) -> Result<Vec<Holding>> {
+    if holdings.is_empty() {
+        bail!("The portfolio has no holdings");
+    }
+    if holdings.len() > MAX_HOLDINGS {
+        bail!("The portfolio has {} holdings, at most {} are supported", holdings.len(), MAX_HOLDINGS);
+    }
+    for holding in &mut holdings {
+        holding.currency = holding.currency.trim().to_uppercase();
+        if !currency::is_currency_code(&holding.currency) {
+            bail!("{} is not a three-letter currency code", holding.currency);
+        }
+        if !holding.amount.is_finite() {
+            bail!("Invalid amount for {}", holding.currency);
+        }
+    }
+    Ok(holdings)
+}
+
+/// Where the rates of a valuation came from.
+#[derive(Clone, Serialize)]
+pub struct RateSource {
+    pub date: Option<String>,
+    pub provider: Option<String>,
+    /// Provider's publication time (Unix seconds), when it reports one
+    pub published_at: Option<u64>,
+}
+
+impl RateSource {
+    fn of(rates: &ExchangeRates) -> Self {
+        Self {
+            date: rates.date.clone(),
+            provider: rates.provider.clone(),
+            published_at: rates.timestamp,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Position {
+    #[serde(flatten)]
+    pub holding: Holding,
+    /// Units of the reporting currency per unit of the holding's currency
+    pub rate: f64,
+    pub value: f64,
+    /// Share of the total value, 0 when the total is 0
+    pub weight: f64,
+    /// Value at the comparison rates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_value: Option<f64>,
+}
+
+impl Position {
+    /// Change in value since the comparison date, due to exchange rates alone.
+    pub fn gain(&self) -> Option<f64> {
+        self.previous_value.map(|previous| self.value - previous)
+    }
+}
+
+#[derive(Serialize)]
+pub struct Comparison {
+    pub rates: RateSource,
+    pub total: f64,
+}
+
+#[derive(Serialize)]
+pub struct Valuation {
+    pub currency: String,
+    pub rates: RateSource,
+    pub positions: Vec<Position>,
+    pub total: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous: Option<Comparison>,
+}
+
+impl Valuation {
+    /// Values `holdings` in `currency` at `rates`, and at `previous` rates when given.
+    pub fn new(holdings: Vec<Holding>, currency: &str, rates: &ExchangeRates, previous: Option<&ExchangeRates>) -> Result<Self> {
+        let currency = currency.to_uppercase();
+        let mut positions = holdings
+            .into_iter()
+            .map(|holding| {
+                let rate = expr::cross_rate(rates, &holding.currency, &currency)?;
+                let previous_value = previous
+                    .map(|previous| expr::cross_rate(previous, &holding.currency, &currency))
+                    .transpose()?
+                    .map(|previous_rate| holding.amount * previous_rate);
+                Ok(Position {
+                    value: holding.amount * rate,
+                    holding,
+                    rate,
+                    weight: 0.0,
+                    previous_value,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let total: f64 = positions.iter().map(|position| position.value).sum();
+        if total != 0.0 {
+            for position in &mut positions {
+                position.weight = position.value / total;
+            }
+        }
+        let previous = previous.map(|previous| Comparison {
+            rates: RateSource::of(previous),
+            total: positions.iter().filter_map(|position| position.previous_value).sum(),
+        });
+
+        Ok(Self {
+            currency,
+            rates: RateSource::of(rates),
+            positions,
+            total,
+            previous,
+        })
+    }
+
+    /// Change in total value since the comparison date.
+    pub fn gain(&self) -> Option<f64> {
+        self.previous.as_ref().map(|previous| self.total - previous.total)
+    }
+
+    /// [`Valuation::gain`] as a fraction of the previous total.
+    pub fn gain_ratio(&self) -> Option<f64> {
+        let previous = self.previous.as_ref()?;
+        (previous.total != 0.0).then(|| (self.total - previous.total) / previous.total.abs())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn rates(date: &str, usd: f64, gbp: f64) -> ExchangeRates {
+        ExchangeRates {
+            success: true,
+            timestamp: None,
+            base: Some("EUR".to_string()),
+            date: Some(date.to_string()),
+            rates: HashMap::from([("USD".to_string(), usd), ("GBP".to_string(), gbp)]),
+            provider: Some("api.frankfurter.app".to_string()),
+            fetched_at: None,
+        }
+    }
+
+    #[test]
+    fn parses_csv_and_toml_holdings() {
+        let csv = "currency,amount,label\nusd, 1_000 ,\"Checking, US\"\n\nGBP,250.5\n";
+        let toml = "[[holdings]]\ncurrency = \"usd\"\namount = 1000\nlabel = \"Checking, US\"\n\n\
+                    [[holdings]]\ncurrency = \"GBP\"\namount = 250.5\n";
+        let expected = vec![
+            Holding { currency: "USD".to_string(), amount: 1000.0, label: Some("Checking, US".to_string()) },
+            Holding { currency: "GBP".to_string(), amount: 250.5, label: None },
+        ];
+        assert_eq!(from_csv(csv).unwrap(), expected);
+        assert_eq!(from_toml(toml).unwrap(), expected);
+
+        assert!(from_csv("USD,lots").unwrap_err().to_string().contains("Line 1"));
+        assert!(from_csv("US$,10").is_err());
+        assert!(from_csv("currency,amount\n").is_err());
+    }
+
+    #[test]
+    fn values_holdings_from_one_table_and_compares() {
+        let holdings = from_csv("EUR,100\nUSD,110\nGBP,-17").unwrap();
+        let valuation =
+            Valuation::new(holdings, "eur", &rates("2024-06-10", 1.1, 0.85), Some(&rates("2024-05-10", 1.0, 0.85)))
+                .unwrap();
+
+        assert_eq!(valuation.currency, "EUR");
+        let values: Vec<f64> = valuation.positions.iter().map(|position| position.value).collect();
+        assert!((values[0] - 100.0).abs() < 1e-9 && (values[1] - 100.0).abs() < 1e-9 && (values[2] + 20.0).abs() < 1e-9);
+        assert!((valuation.total - 180.0).abs() < 1e-9);
+        assert!((valuation.positions[1].weight - 100.0 / 180.0).abs() < 1e-9);
+
+        // Only the dollar moved: 110 USD was worth 110 EUR on the comparison date
+        assert!((valuation.positions[1].gain().unwrap() + 10.0).abs() < 1e-9);
+        assert_eq!(valuation.positions[0].gain(), Some(0.0));
+        assert!((valuation.gain().unwrap() + 10.0).abs() < 1e-9);
+        assert!((valuation.gain_ratio().unwrap() + 10.0 / 190.0).abs() < 1e-9);
+    }
+}
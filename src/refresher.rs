@@ -0,0 +1,166 @@
+//! Background task that keeps the rate cache warm in web mode.
+//!
+//! Configured bases are refreshed at startup, then on a fixed interval and at
+//! daily wall-clock times in a given timezone, such as shortly after the ECB
+//! publishes its reference rates around 16:00 CET. Bases with webhook
+//! subscriptions are refreshed alongside them, as deliveries follow refreshes.
+
+use chrono::{DateTime, Days, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use std::{sync::Arc, time::Duration};
+use tokio::time::Instant;
+
+use crate::{alerts::Alerts, cache::RateCache, webhooks::Webhooks};
+
+pub struct Schedule {
+    /// Refresh this long after the previous refresh
+    pub interval: Option<Duration>,
+    /// Also refresh at these local times every day
+    pub daily_at: Vec<NaiveTime>,
+    pub timezone: Tz,
+}
+
+impl Schedule {
+    /// The first daily refresh time strictly after `now`. Times that fall in a
+    /// DST gap are skipped for that day.
+    fn next_daily(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let today = now.with_timezone(&self.timezone).date_naive();
+
+        (0..=2)
+            .filter_map(|offset| today.checked_add_days(Days::new(offset)))
+            .flat_map(|date| self.daily_at.iter().map(move |time| date.and_time(*time)))
+            .filter_map(|local| self.timezone.from_local_datetime(&local).earliest())
+            .map(|time| time.with_timezone(&Utc))
+            .filter(|time| *time > now)
+            .min()
+    }
+}
+
+pub struct Refresher {
+    pub cache: Arc<RateCache>,
+    pub bases: Vec<String>,
+    pub schedule: Schedule,
+    /// Alert rules evaluated after every refresh
+    pub alerts: Option<Arc<Alerts>>,
+    /// Subscribers pushed each newly published rate table
+    pub webhooks: Option<Arc<Webhooks>>,
+}
+
+impl Refresher {
+    /// The configured bases followed by any other base with webhook subscriptions.
+    fn bases(&self) -> Vec<String> {
+        let mut bases = self.bases.clone();
+        if let Some(webhooks) = &self.webhooks {
+            for base in webhooks.subscribed_bases() {
+                if !bases.contains(&base) {
+                    bases.push(base);
+                }
+            }
+        }
+        bases
+    }
+
+    async fn refresh_all(&self) {
+        for base in &self.bases() {
+            match self.cache.refresh(base).await {
+                Ok(rates) => {
+                    tracing::info!(
+                        base = %base,
+                        provider = rates.provider.as_deref().unwrap_or("unknown"),
+                        "Refreshed rates"
+                    );
+                    if let Some(webhooks) = &self.webhooks {
+                        webhooks.publish(&rates);
+                    }
+                }
+                Err(e) => tracing::warn!(base = %base, "Failed to refresh rates: {:#}", e),
+            }
+        }
+    }
+
+    /// Refreshes immediately, then on schedule until the task is aborted.
+    pub async fn run(self) {
+        loop {
+            self.refresh_all().await;
+            if let Some(alerts) = &self.alerts {
+                alerts.evaluate(&self.cache).await;
+            }
+
+            let now = Utc::now();
+            let until_daily = self
+                .schedule
+                .next_daily(now)
+                .and_then(|next| (next - now).to_std().ok());
+            let delay = match (self.schedule.interval, until_daily) {
+                (Some(interval), Some(daily)) => interval.min(daily),
+                (Some(delay), None) | (None, Some(delay)) => delay,
+                (None, None) => return,
+            };
+
+            tracing::debug!(seconds = delay.as_secs(), "Next rate refresh scheduled");
+            tokio::time::sleep_until(Instant::now() + delay).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webhooks::{NewSubscription, WebhookStore};
+
+    fn schedule(times: &[&str]) -> Schedule {
+        Schedule {
+            interval: None,
+            daily_at: times.iter().map(|time| time.parse().unwrap()).collect(),
+            timezone: chrono_tz::CET,
+        }
+    }
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn next_daily_follows_local_time_across_dst() {
+        let schedule = schedule(&["16:05"]);
+        // Winter: CET is UTC+1
+        assert_eq!(schedule.next_daily(utc("2024-01-15T10:00:00Z")), Some(utc("2024-01-15T15:05:00Z")));
+        // Summer: CEST is UTC+2
+        assert_eq!(schedule.next_daily(utc("2024-07-15T10:00:00Z")), Some(utc("2024-07-15T14:05:00Z")));
+    }
+
+    #[test]
+    fn next_daily_rolls_over_to_the_next_day() {
+        let schedule = schedule(&["16:05", "08:00"]);
+        assert_eq!(schedule.next_daily(utc("2024-01-15T15:05:00Z")), Some(utc("2024-01-16T07:00:00Z")));
+        assert_eq!(schedule.next_daily(utc("2024-01-15T07:30:00Z")), Some(utc("2024-01-15T15:05:00Z")));
+    }
+
+    #[test]
+    fn next_daily_skips_times_in_a_dst_gap() {
+        // 02:30 does not exist on the spring-forward day
+        let schedule = schedule(&["02:30"]);
+        assert_eq!(schedule.next_daily(utc("2024-03-30T12:00:00Z")), Some(utc("2024-04-01T00:30:00Z")));
+    }
+
+    #[test]
+    fn empty_schedule_has_no_daily_refresh() {
+        assert_eq!(schedule(&[]).next_daily(utc("2024-01-15T10:00:00Z")), None);
+    }
+
+    #[test]
+    fn subscribed_bases_are_refreshed_too() {
+        let store = Arc::new(WebhookStore::in_memory().unwrap());
+        for base in ["pln", "EUR", "PLN"] {
+            store.add(NewSubscription { url: "https://example.com/hook".to_string(), base: base.to_string() }).unwrap();
+        }
+        let refresher = Refresher {
+            cache: Arc::new(RateCache::new(String::new(), Duration::from_secs(600))),
+            bases: vec!["EUR".to_string(), "USD".to_string()],
+            schedule: schedule(&[]),
+            alerts: None,
+            webhooks: Some(Arc::new(Webhooks::new(store, Default::default(), false))),
+        };
+        assert_eq!(refresher.bases(), ["EUR", "USD", "PLN"]);
+    }
+}
@@ -0,0 +1,323 @@
+//! Local SQLite store of fetched rate snapshots.
+//!
+//! Every rate table fetched from a real provider is recorded with its
+//! provider, base, publication time and fetch time, giving an audit trail of
+//! which rates were in use when, and a source for historical conversions.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::{Connection, OptionalExtension, Row, params};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use crate::{ExchangeRates, MOCK_PROVIDER};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS snapshots (
+        id           INTEGER PRIMARY KEY,
+        provider     TEXT    NOT NULL,
+        base         TEXT    NOT NULL,
+        published_at INTEGER,
+        rate_date    TEXT    NOT NULL,
+        fetched_at   INTEGER NOT NULL,
+        rates        TEXT    NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS snapshots_by_date ON snapshots (rate_date, base);
+    CREATE INDEX IF NOT EXISTS snapshots_by_provider ON snapshots (provider, fetched_at);
+";
+
+const COLUMNS: &str = "id, provider, base, published_at, rate_date, fetched_at, rates";
+
+/// A stored rate table.
+pub struct Snapshot {
+    pub id: i64,
+    pub provider: String,
+    pub base: String,
+    /// Provider's publication time (Unix seconds); None for providers that
+    /// only report the date the rates apply to, never the fetch time
+    pub published_at: Option<i64>,
+    /// Date the rates apply to
+    pub rate_date: NaiveDate,
+    /// When the rates were fetched (Unix seconds)
+    pub fetched_at: i64,
+    pub rates: HashMap<String, f64>,
+}
+
+impl Snapshot {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let rate_date: String = row.get(4)?;
+        let rates: String = row.get(6)?;
+        Ok(Self {
+            id: row.get(0)?,
+            provider: row.get(1)?,
+            base: row.get(2)?,
+            published_at: row.get(3)?,
+            rate_date: rate_date.parse().map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(4, rusqlite::types::Type::Text, Box::new(e))
+            })?,
+            fetched_at: row.get(5)?,
+            rates: serde_json::from_str(&rates).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(6, rusqlite::types::Type::Text, Box::new(e))
+            })?,
+        })
+    }
+
+    pub fn into_exchange_rates(self) -> ExchangeRates {
+        ExchangeRates {
+            success: true,
+            timestamp: self.published_at.map(|published_at| published_at as u64),
+            base: Some(self.base),
+            date: Some(self.rate_date.to_string()),
+            rates: self.rates,
+            provider: Some(self.provider),
+            fetched_at: Some(self.fetched_at as u64),
+        }
+    }
+}
+
+/// Filters for [`SnapshotStore::query`]; unset fields match everything.
+#[derive(Default)]
+pub struct SnapshotQuery {
+    pub date: Option<NaiveDate>,
+    pub provider: Option<String>,
+    pub base: Option<String>,
+    pub limit: usize,
+}
+
+pub struct SnapshotStore {
+    conn: Mutex<Connection>,
+}
+
+/// `$XDG_DATA_HOME/currency-converter/rates.db`, falling back to `~/.local/share`
+/// and finally the current directory.
+pub fn default_path() -> PathBuf {
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")));
+
+    match data_home {
+        Some(dir) => dir.join("currency-converter").join("rates.db"),
+        None => PathBuf::from("currency-converter.db"),
+    }
+}
+
+/// Opens the SQLite database at `path`, creating its directory. Several
+/// processes (CLI runs, the web server) may write to it concurrently.
+pub fn open_connection(path: &Path) -> Result<Connection> {
+    if let Some(parent) = path.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        std::fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+
+    let conn = Connection::open(path).with_context(|| format!("Failed to open database {}", path.display()))?;
+    conn.busy_timeout(std::time::Duration::from_secs(5))?;
+    conn.pragma_update(None, "journal_mode", "WAL")?;
+
+    Ok(conn)
+}
+
+/// The date a rate table applies to: the provider's reference date when it
+/// gives one (e.g. the ECB's last business day), else the publication date.
+pub fn rate_date(rates: &ExchangeRates) -> NaiveDate {
+    rates
+        .date
+        .as_deref()
+        .and_then(|date| date.parse().ok())
+        .or_else(|| {
+            rates
+                .timestamp
+                .and_then(|timestamp| DateTime::<Utc>::from_timestamp(timestamp as i64, 0))
+                .map(|published| published.date_naive())
+        })
+        .unwrap_or_else(|| Utc::now().date_naive())
+}
+
+impl SnapshotStore {
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::init(open_connection(path)?)
+    }
+
+    #[cfg(test)]
+    pub fn in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA).context("Failed to create snapshot tables")?;
+
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Stores a fetched rate table, returning its id. Mock data is not recorded.
+    pub fn record(&self, rates: &ExchangeRates) -> Result<Option<i64>> {
+        let provider = match rates.provider.as_deref() {
+            Some(MOCK_PROVIDER) | None => return Ok(None),
+            Some(provider) => provider,
+        };
+        let base = rates.base.as_deref().context("Rate table has no base currency")?;
+
+        let conn = self.conn();
+        conn.execute(
+            "INSERT INTO snapshots (provider, base, published_at, rate_date, fetched_at, rates)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                provider,
+                base,
+                rates.timestamp.map(|timestamp| timestamp as i64),
+                rate_date(rates).to_string(),
+                rates.fetched_at.map_or_else(|| Utc::now().timestamp(), |fetched_at| fetched_at as i64),
+                serde_json::to_string(&rates.rates)?,
+            ],
+        )?;
+
+        Ok(Some(conn.last_insert_rowid()))
+    }
+
+    /// Snapshots matching `query`, newest first.
+    pub fn query(&self, query: &SnapshotQuery) -> Result<Vec<Snapshot>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(&format!(
+            "SELECT {} FROM snapshots
+             WHERE (?1 IS NULL OR rate_date = ?1)
+               AND (?2 IS NULL OR provider = ?2)
+               AND (?3 IS NULL OR base = ?3)
+             ORDER BY fetched_at DESC, id DESC
+             LIMIT ?4",
+            COLUMNS
+        ))?;
+
+        let snapshots = statement
+            .query_map(
+                params![
+                    query.date.map(|date| date.to_string()),
+                    query.provider,
+                    query.base.as_ref().map(|base| base.to_uppercase()),
+                    query.limit as i64,
+                ],
+                Snapshot::from_row,
+            )?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(snapshots)
+    }
+
+    /// The latest snapshot for `date`, preferring one with the given base
+    /// (any base works for cross rates as long as both currencies are listed).
+    pub fn on_date(&self, date: NaiveDate, base: &str) -> Result<Option<Snapshot>> {
+        let conn = self.conn();
+        let snapshot = conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM snapshots WHERE rate_date = ?1
+                     ORDER BY base = ?2 DESC, fetched_at DESC, id DESC LIMIT 1",
+                    COLUMNS
+                ),
+                params![date.to_string(), base.to_uppercase()],
+                Snapshot::from_row,
+            )
+            .optional()?;
+
+        Ok(snapshot)
+    }
+
+    /// The latest snapshot fetched at or before `time` (Unix seconds); of
+    /// snapshots fetched in the same second, one with the given base.
+    pub fn latest_before(&self, time: i64, base: &str) -> Result<Option<Snapshot>> {
+        let conn = self.conn();
+        let snapshot = conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM snapshots WHERE fetched_at <= ?1
+                     ORDER BY fetched_at DESC, base = ?2 DESC, id DESC LIMIT 1",
+                    COLUMNS
+                ),
+                params![time, base.to_uppercase()],
+                Snapshot::from_row,
+            )
+            .optional()?;
+
+        Ok(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rates(provider: &str, base: &str, date: &str, usd: f64) -> ExchangeRates {
+        ExchangeRates {
+            success: true,
+            timestamp: Some(1_718_000_000),
+            base: Some(base.to_string()),
+            date: Some(date.to_string()),
+            rates: HashMap::from([("USD".to_string(), usd), ("GBP".to_string(), 0.85)]),
+            provider: Some(provider.to_string()),
+            fetched_at: None,
+        }
+    }
+
+    #[test]
+    fn records_and_queries_by_date_and_provider() {
+        let store = SnapshotStore::in_memory().unwrap();
+        store.record(&rates("api.frankfurter.app", "EUR", "2024-06-10", 1.07)).unwrap();
+        store.record(&rates("open.er-api.com", "EUR", "2024-06-11", 1.08)).unwrap();
+
+        let by_date = store
+            .query(&SnapshotQuery { date: "2024-06-10".parse().ok(), limit: 10, ..Default::default() })
+            .unwrap();
+        assert_eq!(by_date.len(), 1);
+        assert_eq!(by_date[0].provider, "api.frankfurter.app");
+        assert_eq!(by_date[0].rates["USD"], 1.07);
+
+        let by_provider = store
+            .query(&SnapshotQuery { provider: Some("open.er-api.com".to_string()), limit: 10, ..Default::default() })
+            .unwrap();
+        assert_eq!(by_provider.len(), 1);
+        assert_eq!(by_provider[0].rate_date.to_string(), "2024-06-11");
+    }
+
+    #[test]
+    fn publication_time_is_only_stored_when_reported() {
+        let store = SnapshotStore::in_memory().unwrap();
+        store.record(&rates("open.er-api.com", "EUR", "2024-06-10", 1.07)).unwrap();
+        store
+            .record(&ExchangeRates {
+                timestamp: None,
+                fetched_at: Some(1_718_000_600),
+                ..rates("api.frankfurter.app", "EUR", "2024-06-10", 1.07)
+            })
+            .unwrap();
+
+        let snapshots = store.query(&SnapshotQuery { limit: 10, ..Default::default() }).unwrap();
+        let by_provider = |provider: &str| snapshots.iter().find(|snapshot| snapshot.provider == provider).unwrap();
+        assert_eq!(by_provider("open.er-api.com").published_at, Some(1_718_000_000));
+        assert_eq!(by_provider("api.frankfurter.app").published_at, None);
+        assert_eq!(by_provider("api.frankfurter.app").fetched_at, 1_718_000_600);
+    }
+
+    #[test]
+    fn mock_rates_are_not_recorded() {
+        let store = SnapshotStore::in_memory().unwrap();
+        assert_eq!(store.record(&rates(MOCK_PROVIDER, "EUR", "2024-06-10", 1.0)).unwrap(), None);
+        assert!(store.query(&SnapshotQuery { limit: 10, ..Default::default() }).unwrap().is_empty());
+    }
+
+    #[test]
+    fn on_date_prefers_matching_base() {
+        let store = SnapshotStore::in_memory().unwrap();
+        store.record(&rates("api.frankfurter.app", "USD", "2024-06-10", 1.0)).unwrap();
+        store.record(&rates("open.er-api.com", "EUR", "2024-06-10", 1.07)).unwrap();
+
+        let snapshot = store.on_date("2024-06-10".parse().unwrap(), "usd").unwrap().unwrap();
+        assert_eq!(snapshot.base, "USD");
+        assert!(store.on_date("2024-06-09".parse().unwrap(), "USD").unwrap().is_none());
+    }
+}
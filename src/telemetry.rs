@@ -0,0 +1,185 @@
+//! OpenTelemetry trace export over OTLP/HTTP, and W3C trace-context
+//! propagation for incoming requests and outbound provider calls.
+//!
+//! Spans are produced by `tracing`; when an OTLP endpoint is configured the
+//! `tracing-opentelemetry` layer turns them into OpenTelemetry spans.
+
+use anyhow::{Context as _, Result};
+use axum::{body::Body, http::Request};
+use opentelemetry::{
+    global,
+    propagation::{Extractor, Injector},
+    trace::TracerProvider as _,
+};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{
+    Resource,
+    propagation::TraceContextPropagator,
+    trace::{SdkTracer, SdkTracerProvider},
+};
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+pub const SERVICE_NAME: &str = "currency-converter";
+
+/// Accepts either a collector base URL (`http://collector:4318`) or the full traces URL.
+fn traces_endpoint(endpoint: &str) -> String {
+    let endpoint = endpoint.trim_end_matches('/');
+    if endpoint.ends_with("/v1/traces") {
+        endpoint.to_string()
+    } else {
+        format!("{}/v1/traces", endpoint)
+    }
+}
+
+/// Creates a tracer provider exporting batches to `endpoint` and installs the
+/// W3C trace-context propagator. Must be called outside the tokio runtime,
+/// since the exporter uses a blocking HTTP client on its own thread.
+pub fn init_provider(endpoint: &str) -> Result<SdkTracerProvider> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(traces_endpoint(endpoint))
+        .build()
+        .with_context(|| format!("Failed to create OTLP exporter for {}", endpoint))?;
+
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(SERVICE_NAME).build())
+        .build())
+}
+
+pub fn tracer(provider: &SdkTracerProvider) -> SdkTracer {
+    provider.tracer(SERVICE_NAME)
+}
+
+struct ReqwestHeaders<'a>(&'a mut reqwest::header::HeaderMap);
+
+impl Injector for ReqwestHeaders<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let Ok(name) = reqwest::header::HeaderName::from_bytes(key.as_bytes())
+            && let Ok(value) = reqwest::header::HeaderValue::from_str(&value)
+        {
+            self.0.insert(name, value);
+        }
+    }
+}
+
+struct AxumHeaders<'a>(&'a axum::http::HeaderMap);
+
+impl Extractor for AxumHeaders<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+/// Adds `traceparent`/`tracestate` for the current span to outbound request headers.
+pub fn inject_context(headers: &mut reqwest::header::HeaderMap) {
+    let context = Span::current().context();
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&context, &mut ReqwestHeaders(headers)));
+}
+
+/// Span for an incoming request, continuing the caller's trace when it sent `traceparent`.
+pub fn make_request_span(request: &Request<Body>) -> Span {
+    let span = tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        version = ?request.version(),
+        otel.kind = "server",
+    );
+
+    let parent = global::get_text_map_propagator(|propagator| propagator.extract(&AxumHeaders(request.headers())));
+    // Fails only when no OpenTelemetry layer is installed, in which case there is nothing to link
+    let _ = span.set_parent(parent);
+
+    span
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::mpsc,
+        thread,
+        time::Duration,
+    };
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// Minimal in-process OTLP/HTTP collector: accepts requests and forwards their bodies.
+    fn start_collector() -> (String, mpsc::Receiver<Vec<u8>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':')
+                        && name.eq_ignore_ascii_case("content-length")
+                    {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").unwrap();
+                if sender.send(body).is_err() {
+                    break;
+                }
+            }
+        });
+
+        (endpoint, receiver)
+    }
+
+    #[test]
+    fn exports_spans_to_collector_and_propagates_context() {
+        let (endpoint, received) = start_collector();
+        let provider = init_provider(&endpoint).unwrap();
+        let subscriber = tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer(&provider)));
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("provider_fetch", provider = "api.frankfurter.app");
+            let _entered = span.enter();
+            inject_context(&mut headers);
+        });
+        provider.force_flush().unwrap();
+
+        let traceparent = headers.get("traceparent").expect("traceparent header").to_str().unwrap();
+        let parts: Vec<_> = traceparent.split('-').collect();
+        assert_eq!(parts.len(), 4);
+        assert_eq!(parts[0], "00");
+        assert_eq!(parts[1].len(), 32);
+
+        let body = received.recv_timeout(Duration::from_secs(10)).expect("collector received spans");
+        let contains = |needle: &[u8]| body.windows(needle.len()).any(|window| window == needle);
+        assert!(contains(b"provider_fetch"));
+        assert!(contains(SERVICE_NAME.as_bytes()));
+
+        provider.shutdown().unwrap();
+    }
+
+    #[test]
+    fn traces_endpoint_appends_signal_path() {
+        assert_eq!(traces_endpoint("http://collector:4318"), "http://collector:4318/v1/traces");
+        assert_eq!(traces_endpoint("http://collector:4318/"), "http://collector:4318/v1/traces");
+        assert_eq!(traces_endpoint("http://collector:4318/v1/traces"), "http://collector:4318/v1/traces");
+    }
+}
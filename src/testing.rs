@@ -0,0 +1,53 @@
+//! Helpers shared by tests.
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    sync::mpsc,
+    thread,
+};
+
+/// A request received by [`http_sink`], with lowercase header names.
+pub struct SinkRequest {
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+/// Local HTTP server that answers requests with `statuses` in turn
+/// (repeating the last one) and forwards them to the returned receiver.
+pub fn http_sink(statuses: &[u16]) -> (String, mpsc::Receiver<SinkRequest>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let statuses = statuses.to_vec();
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        for (index, stream) in listener.incoming().enumerate() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut headers = HashMap::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+                }
+            }
+            let content_length = headers.get("content-length").map_or(0, |length| length.parse().unwrap());
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+
+            let status = statuses.get(index).or(statuses.last()).copied().unwrap_or(204);
+            write!(stream, "HTTP/1.1 {} Sink\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status).unwrap();
+            if sender.send(SinkRequest { headers, body }).is_err() {
+                break;
+            }
+        }
+    });
+
+    (url, receiver)
+}
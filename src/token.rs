@@ -0,0 +1,10 @@
+//! Random tokens: CSRF and session cookies, CSP nonces, webhook ids and secrets.
+
+use rand::RngCore;
+
+/// `bytes` random bytes from the thread-local CSPRNG, hex-encoded.
+pub fn random_hex(bytes: usize) -> String {
+    let mut buffer = vec![0u8; bytes];
+    rand::thread_rng().fill_bytes(&mut buffer);
+    hex::encode(buffer)
+}
@@ -0,0 +1,290 @@
+//! JSON API for managing rate alert rules and webhook subscriptions, and a
+//! Server-Sent Events stream of rate changes.
+//!
+//! Changing alert rules and every webhook endpoint, including reading
+//! subscriptions (their URLs may embed credentials), take an [`AdminKey`].
+//! Errors are returned as `{"error": "..."}`.
+
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State, rejection::JsonRejection},
+    http::StatusCode,
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+    routing::{delete, get},
+};
+use futures_util::{Stream, StreamExt, future::ready};
+use serde::Deserialize;
+use serde_json::json;
+use std::{collections::BTreeMap, convert::Infallible, sync::Arc};
+
+use super::{AppState, auth::AdminKey, stream};
+use crate::alerts::{AlertStore, NewRule};
+use crate::currency::is_currency_code;
+use crate::outbound;
+use crate::webhooks::{self, NewSubscription, WebhookStore};
+
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        Self(StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e))
+    }
+}
+
+fn alert_store(state: &AppState) -> Result<&AlertStore, ApiError> {
+    state.alerts.as_deref().ok_or_else(|| {
+        ApiError(StatusCode::SERVICE_UNAVAILABLE, "Alerts need the database, which is disabled".to_string())
+    })
+}
+
+fn webhook_store(state: &AppState) -> Result<&WebhookStore, ApiError> {
+    state.webhooks.as_deref().ok_or_else(|| {
+        ApiError(StatusCode::SERVICE_UNAVAILABLE, "Webhooks need the database, which is disabled".to_string())
+    })
+}
+
+async fn list_alerts(State(state): State<Arc<AppState>>) -> Result<Response, ApiError> {
+    Ok(Json(alert_store(&state)?.list()?).into_response())
+}
+
+async fn create_alert(
+    State(state): State<Arc<AppState>>,
+    _: AdminKey,
+    rule: Result<Json<NewRule>, JsonRejection>,
+) -> Result<Response, ApiError> {
+    let store = alert_store(&state)?;
+    let Json(rule) = rule.map_err(|rejection| ApiError(rejection.status(), rejection.body_text()))?;
+
+    let rule = store
+        .add(rule)
+        .map_err(|e| ApiError(StatusCode::UNPROCESSABLE_ENTITY, format!("{:#}", e)))?;
+    Ok((StatusCode::CREATED, Json(rule)).into_response())
+}
+
+async fn delete_alert(
+    State(state): State<Arc<AppState>>,
+    _: AdminKey,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, ApiError> {
+    if alert_store(&state)?.remove(id)? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError(StatusCode::NOT_FOUND, format!("No alert rule #{}", id)))
+    }
+}
+
+async fn list_webhooks(State(state): State<Arc<AppState>>, _: AdminKey) -> Result<Response, ApiError> {
+    Ok(Json(webhook_store(&state)?.list()?).into_response())
+}
+
+/// Registers a subscription; the response carries its signing secret, which is not shown again.
+async fn create_webhook(
+    State(state): State<Arc<AppState>>,
+    _: AdminKey,
+    subscription: Result<Json<NewSubscription>, JsonRejection>,
+) -> Result<Response, ApiError> {
+    let store = webhook_store(&state)?;
+    let Json(subscription) =
+        subscription.map_err(|rejection| ApiError(rejection.status(), rejection.body_text()))?;
+
+    // Checked again before each delivery, as DNS may change after registration
+    let unprocessable = |e: anyhow::Error| ApiError(StatusCode::UNPROCESSABLE_ENTITY, format!("{:#}", e));
+    let url = outbound::parse(&subscription.url).map_err(unprocessable)?;
+    outbound::resolve(&url, state.allow_private_webhooks, webhooks::DELIVERY_TIMEOUT)
+        .await
+        .map_err(unprocessable)?;
+
+    let subscription = store.add(subscription).map_err(unprocessable)?;
+    Ok((StatusCode::CREATED, Json(subscription)).into_response())
+}
+
+async fn delete_webhook(
+    State(state): State<Arc<AppState>>,
+    _: AdminKey,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, ApiError> {
+    if webhook_store(&state)?.remove(id)? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError(StatusCode::NOT_FOUND, format!("No webhook subscription #{}", id)))
+    }
+}
+
+async fn list_dead_letters(State(state): State<Arc<AppState>>, _: AdminKey) -> Result<Response, ApiError> {
+    Ok(Json(webhook_store(&state)?.dead_letters()?).into_response())
+}
+
+async fn delete_dead_letter(
+    State(state): State<Arc<AppState>>,
+    _: AdminKey,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, ApiError> {
+    if webhook_store(&state)?.remove_dead_letter(id)? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError(StatusCode::NOT_FOUND, format!("No dead letter #{}", id)))
+    }
+}
+
+#[derive(Deserialize)]
+struct StreamQuery {
+    base: Option<String>,
+    /// Comma-separated codes to include; all of the base's rates when absent
+    symbols: Option<String>,
+}
+
+/// Streams `rates` events for a base currency: the current table on connect,
+/// then each refresh that changes one of the selected rates.
+async fn stream_rates(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<StreamQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let base = query.base.as_deref().unwrap_or("EUR").trim().to_uppercase();
+    if !is_currency_code(&base) {
+        return Err(ApiError(StatusCode::BAD_REQUEST, "base must be a three-letter currency code".to_string()));
+    }
+    let symbols: Vec<String> = query
+        .symbols
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(|symbol| symbol.trim().to_uppercase())
+        .filter(|symbol| !symbol.is_empty())
+        .collect();
+    if let Some(symbol) = symbols.iter().find(|symbol| !is_currency_code(symbol)) {
+        return Err(ApiError(StatusCode::BAD_REQUEST, format!("{} is not a three-letter currency code", symbol)));
+    }
+
+    let mut last: Option<BTreeMap<String, f64>> = None;
+    let events = stream::rate_updates(state.rates.clone(), base).filter_map(move |rates| {
+        let selected: BTreeMap<String, f64> = rates
+            .rates
+            .iter()
+            .filter(|(code, _)| symbols.is_empty() || symbols.contains(code))
+            .map(|(code, rate)| (code.clone(), *rate))
+            .collect();
+        if last.as_ref() == Some(&selected) {
+            return ready(None);
+        }
+
+        let event = Event::default().event("rates").json_data(json!({
+            "base": rates.base,
+            "provider": rates.provider,
+            "published_at": rates.timestamp,
+            "date": rates.date,
+            "rates": selected,
+        }));
+        last = Some(selected);
+        ready(event.ok().map(Ok))
+    });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+pub fn router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/api/stream", get(stream_rates))
+        .route("/api/alerts", get(list_alerts).post(create_alert))
+        .route("/api/alerts/:id", delete(delete_alert))
+        .route("/api/webhooks", get(list_webhooks).post(create_webhook))
+        .route("/api/webhooks/dead-letters", get(list_dead_letters))
+        .route("/api/webhooks/dead-letters/:id", delete(delete_dead_letter))
+        .route("/api/webhooks/:id", delete(delete_webhook))
+        .with_state(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cache::RateCache, web::RateLimiter};
+    use axum::{
+        body::{Body, to_bytes},
+        http::{Request, header},
+    };
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    fn app(admin_keys: &[&str]) -> Router {
+        router(Arc::new(AppState {
+            rates: Arc::new(RateCache::new(String::new(), Duration::from_secs(600))),
+            locale: None,
+            favourites: Vec::new(),
+            security: Default::default(),
+            rate_limiter: RateLimiter::new(Default::default()),
+            audit: None,
+            history: Default::default(),
+            alerts: Some(Arc::new(AlertStore::in_memory().unwrap())),
+            webhooks: Some(Arc::new(WebhookStore::in_memory().unwrap())),
+            snapshots: None,
+            live_results: false,
+            admin_keys: admin_keys.iter().map(|key| key.to_string()).collect(),
+            allow_private_webhooks: false,
+        }))
+    }
+
+    async fn post(app: Router, uri: &str, key: Option<&str>, body: serde_json::Value) -> (StatusCode, String) {
+        let mut request = Request::post(uri).header(header::CONTENT_TYPE, "application/json");
+        if let Some(key) = key {
+            request = request.header("x-api-key", key);
+        }
+        let response = app.oneshot(request.body(Body::from(body.to_string())).unwrap()).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn webhooks_need_a_configured_admin_key() {
+        let subscription = json!({ "url": "http://8.8.8.8/hook", "base": "EUR" });
+
+        let (status, body) = post(app(&[]), "/api/webhooks", Some("anything"), subscription.clone()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(body.contains("no admin keys configured"));
+
+        let (status, _) = post(app(&["secret"]), "/api/webhooks", None, subscription.clone()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = post(app(&["secret"]), "/api/webhooks", Some("secreT"), subscription.clone()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, body) = post(app(&["other", "secret"]), "/api/webhooks", Some("secret"), subscription).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert!(body.contains("\"secret\":"));
+    }
+
+    #[tokio::test]
+    async fn alert_changes_need_a_configured_admin_key() {
+        let rule = json!({ "base": "EUR", "quote": "UAH", "condition": "above", "threshold": 45 });
+
+        let (status, _) = post(app(&[]), "/api/alerts", Some("anything"), rule.clone()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = post(app(&["secret"]), "/api/alerts", Some("wrong"), rule.clone()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, body) = post(app(&["secret"]), "/api/alerts", Some("secret"), rule).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert!(body.contains("\"quote\":\"UAH\""));
+
+        let response = app(&[])
+            .oneshot(Request::delete("/api/alerts/1").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn webhooks_to_private_addresses_are_refused() {
+        for url in ["http://169.254.169.254/latest/meta-data/", "http://127.0.0.1:8080/hook", "http://[fd00::1]/hook"] {
+            let subscription = json!({ "url": url, "base": "EUR" });
+            let (status, body) = post(app(&["secret"]), "/api/webhooks", Some("secret"), subscription).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", url);
+            assert!(body.contains("not a public address"), "{}: {}", url, body);
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! Static assets embedded from `static/` by `build.rs` and served under `/static`.
+//!
+//! Each asset is reachable by its content-hashed name (`app.3f9c0e1d2b4a.css`),
+//! which is cached for a year, and by its plain name, which is revalidated.
+
+use axum::{
+    extract::Path,
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+
+pub struct Asset {
+    pub path: &'static str,
+    pub hashed_path: &'static str,
+    pub bytes: &'static [u8],
+}
+
+include!(concat!(env!("OUT_DIR"), "/assets.rs"));
+
+/// HTMX and its SSE extension, pinned and vendored into `static/` by `scripts/vendor-htmx.sh`
+const HTMX_PATH: &str = "htmx.min.js";
+const HTMX_SSE_PATH: &str = "htmx-sse.js";
+
+pub fn find(path: &str) -> Option<&'static Asset> {
+    ASSETS.iter().find(|asset| asset.path == path)
+}
+
+/// URL of an embedded asset, using its content-hashed name.
+pub fn url(path: &str) -> String {
+    match find(path) {
+        Some(asset) => format!("/static/{}", asset.hashed_path),
+        None => format!("/static/{}", path),
+    }
+}
+
+pub fn htmx_url() -> String {
+    url(HTMX_PATH)
+}
+
+/// Whether the SSE extension was vendored; `--live-results` needs it.
+pub fn has_htmx_sse() -> bool {
+    find(HTMX_SSE_PATH).is_some()
+}
+
+pub fn htmx_sse_url() -> String {
+    url(HTMX_SSE_PATH)
+}
+
+fn content_type(path: &str) -> &'static str {
+    match path.rsplit_once('.').map(|(_, extension)| extension) {
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("ico") => "image/x-icon",
+        Some("woff2") => "font/woff2",
+        _ => "application/octet-stream",
+    }
+}
+
+pub async fn serve(Path(path): Path<String>) -> Response {
+    let (asset, cache_control) = if let Some(asset) = ASSETS.iter().find(|asset| asset.hashed_path == path) {
+        (asset, "public, max-age=31536000, immutable")
+    } else if let Some(asset) = find(&path) {
+        (asset, "no-cache")
+    } else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    (
+        [
+            (header::CONTENT_TYPE, content_type(asset.path)),
+            (header::CACHE_CONTROL, cache_control),
+        ],
+        asset.bytes,
+    )
+        .into_response()
+}
@@ -0,0 +1,50 @@
+//! Admin authorization for the JSON API.
+//!
+//! Handlers that change alert rules or manage webhooks take an [`AdminKey`],
+//! which requires one of the configured admin keys in the `X-API-Key`
+//! header. With no admin keys configured those endpoints are refused
+//! outright rather than left open.
+
+use axum::{
+    Json, async_trait,
+    extract::FromRequestParts,
+    http::{StatusCode, request::Parts},
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+
+use super::{AppState, rate_limit::API_KEY_HEADER};
+
+/// Proof that the request carries a configured admin key.
+pub struct AdminKey;
+
+/// Whether `key` is one of `keys`, comparing every key in constant time.
+fn is_admin_key(keys: &[String], key: &str) -> bool {
+    keys.iter()
+        .fold(subtle::Choice::from(0), |found, known| found | known.as_bytes().ct_eq(key.as_bytes()))
+        .into()
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for AdminKey {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let reject = |status: StatusCode, message: &str| (status, Json(json!({ "error": message }))).into_response();
+
+        if state.admin_keys.is_empty() {
+            return Err(reject(
+                StatusCode::FORBIDDEN,
+                "The admin API is disabled: the server has no admin keys configured (--admin-keys)",
+            ));
+        }
+        let key = parts.headers.get(API_KEY_HEADER).and_then(|value| value.to_str().ok());
+        if key.is_some_and(|key| is_admin_key(&state.admin_keys, key)) {
+            Ok(Self)
+        } else {
+            Err(reject(StatusCode::UNAUTHORIZED, "A valid admin key is required in the X-API-Key header"))
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! CSRF protection for the UI routes using double-submit tokens.
+//!
+//! Safe requests get a random token in a `SameSite=Strict` cookie (issuing one
+//! when missing) and as a [`CsrfToken`] extension for the page to embed. HTMX
+//! echoes it back in the `X-CSRF-Token` header, and state-changing requests
+//! whose header does not match the cookie are rejected with a 403.
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+
+use super::{AppState, templates};
+use crate::token;
+
+pub const COOKIE_NAME: &str = "csrf_token";
+pub const HEADER_NAME: &str = "x-csrf-token";
+
+/// Token for the current request, to be embedded in rendered pages.
+#[derive(Clone)]
+pub struct CsrfToken(pub String);
+
+fn generate_token() -> String {
+    token::random_hex(32)
+}
+
+/// Value of the cookie `name` from the request's Cookie headers.
+pub fn cookie(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|pair| {
+            let (key, value) = pair.trim().split_once('=')?;
+            (key == name).then(|| value.to_string())
+        })
+}
+
+pub async fn verify(State(state): State<Arc<AppState>>, mut request: Request, next: Next) -> Response {
+    let existing = cookie(request.headers(), COOKIE_NAME).filter(|token| token.len() == 64);
+
+    if !request.method().is_safe() {
+        let submitted = request.headers().get(HEADER_NAME).and_then(|value| value.to_str().ok());
+        let valid = matches!((&existing, submitted), (Some(expected), Some(submitted))
+            if bool::from(expected.as_bytes().ct_eq(submitted.as_bytes())));
+
+        if !valid {
+            return (
+                StatusCode::FORBIDDEN,
+                templates::render_error("This form has expired. Reload the page and try again.".to_string()),
+            )
+                .into_response();
+        }
+        return next.run(request).await;
+    }
+
+    let token = existing.clone().unwrap_or_else(generate_token);
+    request.extensions_mut().insert(CsrfToken(token.clone()));
+    let mut response = next.run(request).await;
+
+    if existing.is_none() {
+        let secure = if state.security.hsts { "; Secure" } else { "" };
+        let cookie = format!("{}={}; Path=/; HttpOnly; SameSite=Strict{}", COOKIE_NAME, token, secure);
+        if let Ok(value) = HeaderValue::from_str(&cookie) {
+            response.headers_mut().append(header::SET_COOKIE, value);
+        }
+    }
+
+    response
+}
@@ -0,0 +1,176 @@
+//! Liveness, readiness and Prometheus endpoints, plus the middleware that
+//! records per-route request metrics.
+
+use axum::{
+    Router,
+    extract::{MatchedPath, Request, State},
+    http::{StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use std::{
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use crate::{MOCK_PROVIDER, cache::RateCache, metrics::metrics};
+
+/// Base fetched to check that a provider is reachable when the cache is cold.
+const PROBE_BASE: &str = "EUR";
+
+/// Minimum time between provider probes, so frequent readiness checks
+/// cannot turn into a stream of upstream requests.
+const PROBE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Longest a provider probe may take; a timeout counts as unreachable.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy)]
+enum Probe {
+    Never,
+    /// A probe is in flight; other checks answer with the previous result
+    Running { previous: Option<bool> },
+    Done { at: Instant, reachable: bool },
+}
+
+/// State of the health endpoints: the rate cache and the latest provider probe.
+pub struct Health {
+    rates: Arc<RateCache>,
+    // Never held across an await, so a slow probe cannot block other checks
+    probe: Mutex<Probe>,
+}
+
+impl Health {
+    pub fn new(rates: Arc<RateCache>) -> Self {
+        Self { rates, probe: Mutex::new(Probe::Never) }
+    }
+
+    fn probe(&self) -> MutexGuard<'_, Probe> {
+        self.probe.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Whether a provider answered the latest probe, starting one when the last
+/// is older than [`PROBE_INTERVAL`]. Only one probe runs at a time, and its
+/// result is neither cached nor broadcast: a probe answered with mock rates
+/// must not replace the real tables being served.
+async fn provider_reachable(health: Arc<Health>) -> bool {
+    {
+        let mut probe = health.probe();
+        match *probe {
+            Probe::Done { at, reachable } if at.elapsed() < PROBE_INTERVAL => return reachable,
+            Probe::Running { previous } => return previous.unwrap_or(false),
+            Probe::Done { reachable, .. } => *probe = Probe::Running { previous: Some(reachable) },
+            Probe::Never => *probe = Probe::Running { previous: None },
+        }
+    }
+
+    // Spawned so the probe completes and records its result even if this request is dropped
+    let probe = tokio::spawn({
+        let health = health.clone();
+        async move {
+            let reachable = match tokio::time::timeout(PROBE_TIMEOUT, health.rates.fetch_uncached(PROBE_BASE)).await {
+                Ok(Ok(rates)) => rates.provider.as_deref() != Some(MOCK_PROVIDER),
+                Ok(Err(_)) | Err(_) => false,
+            };
+            *health.probe() = Probe::Done { at: Instant::now(), reachable };
+            reachable
+        }
+    });
+    probe.await.unwrap_or_else(|_| {
+        *health.probe() = Probe::Done { at: Instant::now(), reachable: false };
+        false
+    })
+}
+
+async fn healthz() -> &'static str {
+    "ok"
+}
+
+/// Ready when the cache holds fresh provider data, or a provider answers a probe.
+async fn readyz(State(health): State<Arc<Health>>) -> Response {
+    if health.rates.is_warm().await {
+        return (StatusCode::OK, "ready").into_response();
+    }
+
+    if provider_reachable(health).await {
+        (StatusCode::OK, "ready").into_response()
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "not ready: no rate provider reachable and cache is cold").into_response()
+    }
+}
+
+async fn prometheus_metrics(State(health): State<Arc<Health>>) -> impl IntoResponse {
+    health.rates.record_rate_ages().await;
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
+        metrics().render(),
+    )
+}
+
+/// Counts and times every request, labelled by the matched route pattern so
+/// arbitrary paths cannot blow up label cardinality.
+pub async fn track(request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    let started = Instant::now();
+    let response = next.run(request).await;
+
+    metrics()
+        .http_requests
+        .with_label_values(&[&method, &route, response.status().as_str()])
+        .inc();
+    metrics()
+        .http_request_duration
+        .with_label_values(&[&method, &route])
+        .observe(started.elapsed().as_secs_f64());
+
+    response
+}
+
+pub fn router(health: Arc<Health>) -> Router {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/metrics", get(prometheus_metrics))
+        .with_state(health)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExchangeRates;
+    use axum::body::{Body, to_bytes};
+    use std::collections::HashMap;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn rate_ages_are_only_labelled_for_iso_bases() {
+        let rates = Arc::new(RateCache::new(String::new(), Duration::from_secs(600)));
+        for base in ["SEK", "QQQ"] {
+            rates
+                .insert(ExchangeRates {
+                    success: true,
+                    timestamp: None,
+                    base: Some(base.to_string()),
+                    date: None,
+                    rates: HashMap::from([("USD".to_string(), 0.1)]),
+                    provider: Some("api.frankfurter.app".to_string()),
+                    fetched_at: None,
+                })
+                .await;
+        }
+
+        let app = router(Arc::new(Health::new(rates)));
+        let response = app.oneshot(axum::http::Request::get("/metrics").body(Body::empty()).unwrap()).await.unwrap();
+        let body = String::from_utf8(to_bytes(response.into_body(), usize::MAX).await.unwrap().to_vec()).unwrap();
+        assert!(body.contains(r#"rate_age_seconds{base="SEK"}"#), "{}", body);
+        assert!(!body.contains(r#"base="QQQ""#));
+    }
+}
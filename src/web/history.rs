@@ -0,0 +1,256 @@
+//! Per-session conversion history for the web UI.
+//!
+//! Browsers get a random `session_id` cookie; the conversions made under it
+//! are kept in memory (most recent [`MAX_ENTRIES`]) so they can be listed,
+//! re-run at current rates and exported as CSV. History does not survive a
+//! restart; the audit log is the durable record.
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderValue, header},
+    middleware::Next,
+    response::Response,
+};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use super::{AppState, csrf, routes::ConversionResult};
+use crate::{audit::AuditRecord, token};
+
+pub const COOKIE_NAME: &str = "session_id";
+
+/// Conversions kept per session, oldest dropped first.
+pub const MAX_ENTRIES: usize = 50;
+/// Drop idle sessions once this many are being tracked.
+const PRUNE_THRESHOLD: usize = 10_000;
+/// Sessions unused for this long are dropped when pruning, and their cookie expires.
+const SESSION_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Session of the current request.
+#[derive(Clone)]
+pub struct SessionId(pub String);
+
+/// A conversion as the user submitted it, to be re-run later.
+#[derive(Clone)]
+pub struct Submission {
+    pub amount: String,
+    pub from: String,
+    pub to: String,
+}
+
+pub struct HistoryEntry {
+    pub id: u64,
+    pub submission: Submission,
+    /// Formatted for the session's locale at the time
+    pub result: ConversionResult,
+    /// Exact values and rate provenance, for the CSV export
+    pub record: AuditRecord,
+}
+
+struct Session {
+    entries: VecDeque<HistoryEntry>,
+    used_at: Instant,
+}
+
+#[derive(Default)]
+pub struct History {
+    sessions: Mutex<HashMap<String, Session>>,
+    next_id: Mutex<u64>,
+}
+
+impl History {
+    pub fn push(&self, session: &str, submission: Submission, result: ConversionResult, record: AuditRecord) {
+        let id = {
+            let mut next_id = self.next_id.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            *next_id += 1;
+            *next_id
+        };
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if sessions.len() >= PRUNE_THRESHOLD && !sessions.contains_key(session) {
+            sessions.retain(|_, session| now.duration_since(session.used_at) < SESSION_TTL);
+            // Still full of active sessions: evict the least recently used one
+            if sessions.len() >= PRUNE_THRESHOLD
+                && let Some(oldest) = sessions.iter().min_by_key(|(_, session)| session.used_at).map(|(key, _)| key.clone())
+            {
+                sessions.remove(&oldest);
+            }
+        }
+
+        let entries = sessions.entry(session.to_string()).or_insert_with(|| Session {
+            entries: VecDeque::new(),
+            used_at: now,
+        });
+        entries.used_at = now;
+        entries.entries.push_front(HistoryEntry { id, submission, result, record });
+        entries.entries.truncate(MAX_ENTRIES);
+    }
+
+    /// Runs `f` on the session's entries, newest first.
+    pub fn with_entries<T>(&self, session: &str, f: impl FnOnce(&[HistoryEntry]) -> T) -> T {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        match sessions.get_mut(session) {
+            Some(session) => {
+                session.used_at = Instant::now();
+                f(session.entries.make_contiguous())
+            }
+            None => f(&[]),
+        }
+    }
+
+    pub fn submission(&self, session: &str, id: u64) -> Option<Submission> {
+        self.with_entries(session, |entries| {
+            entries.iter().find(|entry| entry.id == id).map(|entry| entry.submission.clone())
+        })
+    }
+
+    pub fn clear(&self, session: &str) {
+        self.sessions.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).remove(session);
+    }
+}
+
+fn generate_session_id() -> String {
+    token::random_hex(16)
+}
+
+/// Attaches a [`SessionId`] to every request, issuing the cookie when missing.
+pub async fn session(State(state): State<Arc<AppState>>, mut request: Request, next: Next) -> Response {
+    let existing = csrf::cookie(request.headers(), COOKIE_NAME).filter(|id| id.len() == 32);
+    let id = existing.clone().unwrap_or_else(generate_session_id);
+    request.extensions_mut().insert(SessionId(id.clone()));
+    let mut response = next.run(request).await;
+
+    if existing.is_none() {
+        let secure = if state.security.hsts { "; Secure" } else { "" };
+        let cookie = format!(
+            "{}={}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax{}",
+            COOKIE_NAME,
+            id,
+            SESSION_TTL.as_secs(),
+            secure
+        );
+        if let Ok(value) = HeaderValue::from_str(&cookie) {
+            response.headers_mut().append(header::SET_COOKIE, value);
+        }
+    }
+
+    response
+}
+
+/// Defuses a value a spreadsheet would evaluate as a formula.
+fn defuse(value: &str) -> Cow<'_, str> {
+    if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        Cow::Owned(format!("'{}", value))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+const CSV_HEADER: [&str; 10] =
+    ["recorded_at", "input", "from", "to", "amount", "rate", "result", "rounded_result", "provider", "rate_timestamp"];
+
+/// The session's history as CSV, oldest first.
+pub fn to_csv(entries: &[HistoryEntry]) -> String {
+    let write = || -> csv::Result<Vec<u8>> {
+        let mut writer = csv::WriterBuilder::new().terminator(csv::Terminator::CRLF).from_writer(Vec::new());
+        writer.write_record(CSV_HEADER)?;
+        for entry in entries.iter().rev() {
+            let record = &entry.record;
+            writer.write_record([
+                record.recorded_at.as_str(),
+                &defuse(&record.input),
+                &record.from,
+                &record.to,
+                &record.amount.to_string(),
+                &record.rate.to_string(),
+                &record.result.to_string(),
+                &record.rounded_result,
+                &defuse(record.provider.as_deref().unwrap_or_default()),
+                &record.rate_timestamp.map(|timestamp| timestamp.to_string()).unwrap_or_default(),
+            ])?;
+        }
+        writer.into_inner().map_err(|e| e.into_error().into())
+    };
+    let bytes = write().expect("writing CSV to memory cannot fail");
+    String::from_utf8(bytes).expect("CSV of UTF-8 fields is UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExchangeRates;
+
+    fn push(history: &History, session: &str, amount: &str) {
+        let rates = ExchangeRates {
+            success: true,
+            timestamp: None,
+            base: Some("USD".to_string()),
+            date: None,
+            rates: HashMap::from([("EUR".to_string(), 0.925)]),
+            provider: Some("api.frankfurter.app".to_string()),
+            fetched_at: None,
+        };
+        let submission = Submission {
+            amount: amount.to_string(),
+            from: "USD".to_string(),
+            to: "EUR".to_string(),
+        };
+        let result = ConversionResult {
+            amount: amount.to_string(),
+            from: "USD".to_string(),
+            to: "EUR".to_string(),
+            result: String::new(),
+            rate: "0.925".to_string(),
+            inverse_rate: "1.08108".to_string(),
+            timestamp: String::new(),
+        };
+        let record = AuditRecord::new("web:test".to_string(), amount, "USD", "EUR", 1.0, 0.925, &rates);
+        history.push(session, submission, result, record);
+    }
+
+    #[test]
+    fn sessions_keep_their_most_recent_entries() {
+        let history = History::default();
+        for amount in 0..=MAX_ENTRIES {
+            push(&history, "a", &amount.to_string());
+        }
+        push(&history, "b", "7");
+
+        let (len, newest, oldest) = history.with_entries("a", |entries| {
+            (entries.len(), entries[0].submission.amount.clone(), entries[MAX_ENTRIES - 1].submission.amount.clone())
+        });
+        assert_eq!(len, MAX_ENTRIES);
+        assert_eq!(newest, MAX_ENTRIES.to_string());
+        assert_eq!(oldest, "1");
+
+        let other = history.with_entries("b", |entries| entries[0].id);
+        assert!(history.submission("a", other).is_none());
+        assert_eq!(history.submission("b", other).unwrap().amount, "7");
+    }
+
+    #[test]
+    fn csv_is_quoted_and_defused() {
+        let history = History::default();
+        for amount in ["1,234.56", "say \"hi\"", "=HYPERLINK(\"x\")", "-5"] {
+            push(&history, "a", amount);
+        }
+        let csv = history.with_entries("a", to_csv);
+
+        assert!(csv.starts_with("recorded_at,input,from,to,amount,rate,result,rounded_result,provider,rate_timestamp\r\n"));
+        assert!(csv.contains(",\"1,234.56\",USD,EUR,"));
+        assert!(csv.contains(",\"say \"\"hi\"\"\",USD,"));
+
+        let mut reader = csv::Reader::from_reader(csv.as_bytes());
+        let rows: Vec<csv::StringRecord> = reader.records().collect::<Result<_, _>>().unwrap();
+        let inputs: Vec<&str> = rows.iter().map(|row| &row[1]).collect();
+        assert_eq!(inputs, ["1,234.56", "say \"hi\"", "'=HYPERLINK(\"x\")", "'-5"]);
+        assert_eq!(&rows[0][8], "api.frankfurter.app");
+        // Frankfurter does not report a publication time, so none is exported
+        assert_eq!(&rows[0][9], "");
+    }
+}
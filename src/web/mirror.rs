@@ -0,0 +1,217 @@
+//! Upstream-compatible rate endpoints served from the cache, so existing
+//! open.er-api and Frankfurter clients can point their base URL at us.
+
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::json;
+use std::{collections::BTreeMap, sync::Arc};
+
+use crate::{ExchangeRates, MOCK_PROVIDER, cache::RateCache, currency::is_currency_code, store};
+
+/// Providers publish daily; advertised to clients as the next update time.
+const UPDATE_INTERVAL_SECS: u64 = 24 * 60 * 60;
+
+/// Cached rates for `base`, or `None` when only mock data is available,
+/// which must never be passed off as real rates to mirror clients.
+async fn real_rates(cache: &RateCache, base: &str) -> Option<Arc<ExchangeRates>> {
+    match cache.get(base).await {
+        Ok(rates) if rates.provider.as_deref() != Some(MOCK_PROVIDER) && !rates.rates.is_empty() => Some(rates),
+        Ok(_) => None,
+        Err(e) => {
+            tracing::warn!(base, "Mirror could not load rates: {:#}", e);
+            None
+        }
+    }
+}
+
+fn er_api_error(status: StatusCode, error_type: &str) -> Response {
+    (status, Json(json!({ "result": "error", "error-type": error_type }))).into_response()
+}
+
+/// `GET /v6/latest/{base}` in open.er-api.com's format.
+async fn er_api_latest(State(cache): State<Arc<RateCache>>, Path(base): Path<String>) -> Response {
+    let base = base.to_uppercase();
+    if !is_currency_code(&base) {
+        return er_api_error(StatusCode::NOT_FOUND, "unsupported-code");
+    }
+    let Some(rates) = real_rates(&cache, &base).await else {
+        return er_api_error(StatusCode::SERVICE_UNAVAILABLE, "upstream-unavailable");
+    };
+    let mut table: BTreeMap<&str, f64> = rates.rates.iter().map(|(code, rate)| (code.as_str(), *rate)).collect();
+    table.insert(&base, 1.0);
+
+    // er-api clients expect an update time, so tables from providers that do
+    // not report a publication time give their fetch time instead
+    let updated = rates.timestamp.or(rates.fetched_at).unwrap_or_default();
+    let utc = |timestamp: u64| {
+        DateTime::<Utc>::from_timestamp(timestamp as i64, 0)
+            .unwrap_or_default()
+            .to_rfc2822()
+    };
+
+    Json(json!({
+        "result": "success",
+        "provider": rates.provider,
+        "time_last_update_unix": updated,
+        "time_last_update_utc": utc(updated),
+        "time_next_update_unix": updated + UPDATE_INTERVAL_SECS,
+        "time_next_update_utc": utc(updated + UPDATE_INTERVAL_SECS),
+        "time_eol_unix": 0,
+        "base_code": base,
+        "rates": table,
+    }))
+    .into_response()
+}
+
+#[derive(Deserialize)]
+struct FrankfurterQuery {
+    #[serde(alias = "base")]
+    from: Option<String>,
+    /// Comma-separated symbols to include
+    to: Option<String>,
+    amount: Option<f64>,
+}
+
+fn frankfurter_error(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "message": message }))).into_response()
+}
+
+/// `GET /latest?from=&to=&amount=` in Frankfurter's format.
+async fn frankfurter_latest(State(cache): State<Arc<RateCache>>, Query(query): Query<FrankfurterQuery>) -> Response {
+    let base = query.from.as_deref().unwrap_or("EUR").to_uppercase();
+    let amount = query.amount.unwrap_or(1.0);
+    if !is_currency_code(&base) || !amount.is_finite() {
+        return frankfurter_error(StatusCode::NOT_FOUND, "not found");
+    }
+    let Some(rates) = real_rates(&cache, &base).await else {
+        return frankfurter_error(StatusCode::SERVICE_UNAVAILABLE, "upstream unavailable");
+    };
+
+    let symbols: Option<Vec<String>> = query
+        .to
+        .map(|to| to.split(',').map(|symbol| symbol.trim().to_uppercase()).collect());
+    let table: BTreeMap<&str, f64> = rates
+        .rates
+        .iter()
+        .filter(|(code, _)| **code != base)
+        .filter(|(code, _)| symbols.as_ref().is_none_or(|symbols| symbols.contains(code)))
+        .map(|(code, rate)| (code.as_str(), rate * amount))
+        .collect();
+
+    Json(json!({
+        "amount": amount,
+        "base": base,
+        "date": store::rate_date(&rates).to_string(),
+        "rates": table,
+    }))
+    .into_response()
+}
+
+pub fn router(cache: Arc<RateCache>) -> Router {
+    Router::new()
+        .route("/v6/latest/:base", get(er_api_latest))
+        .route("/latest", get(frankfurter_latest))
+        .with_state(cache)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::{Body, to_bytes},
+        http::Request,
+    };
+    use serde_json::Value;
+    use std::{collections::HashMap, time::Duration};
+    use tower::ServiceExt;
+
+    fn rates(provider: &str) -> ExchangeRates {
+        ExchangeRates {
+            success: true,
+            timestamp: Some(1_718_064_000),
+            base: Some("EUR".to_string()),
+            date: Some("2024-06-11".to_string()),
+            rates: HashMap::from([("USD".to_string(), 1.25), ("GBP".to_string(), 0.5)]),
+            provider: Some(provider.to_string()),
+            fetched_at: None,
+        }
+    }
+
+    async fn get(rates: ExchangeRates, uri: &str) -> (StatusCode, Value) {
+        let cache = Arc::new(RateCache::new(String::new(), Duration::from_secs(600)));
+        cache.insert(rates).await;
+        let response = router(cache).oneshot(Request::get(uri).body(Body::empty()).unwrap()).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    fn keys(value: &Value) -> Vec<&str> {
+        let mut keys: Vec<&str> = value.as_object().unwrap().keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        keys
+    }
+
+    #[tokio::test]
+    async fn er_api_latest_matches_the_upstream_shape() {
+        let (status, body) = get(rates("api.frankfurter.app"), "/v6/latest/eur").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            keys(&body),
+            [
+                "base_code",
+                "provider",
+                "rates",
+                "result",
+                "time_eol_unix",
+                "time_last_update_unix",
+                "time_last_update_utc",
+                "time_next_update_unix",
+                "time_next_update_utc",
+            ]
+        );
+        assert_eq!(body["result"], "success");
+        assert_eq!(body["base_code"], "EUR");
+        assert_eq!(body["time_last_update_unix"], 1_718_064_000);
+        assert_eq!(body["time_last_update_utc"], "Tue, 11 Jun 2024 00:00:00 +0000");
+        assert_eq!(body["time_next_update_unix"], 1_718_150_400);
+        assert_eq!(body["rates"], json!({ "EUR": 1.0, "GBP": 0.5, "USD": 1.25 }));
+
+        let (status, body) = get(rates("api.frankfurter.app"), "/v6/latest/E1R").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body, json!({ "result": "error", "error-type": "unsupported-code" }));
+    }
+
+    #[tokio::test]
+    async fn frankfurter_latest_matches_the_upstream_shape() {
+        let (status, body) = get(rates("open.er-api.com"), "/latest?from=eur&to=usd,gbp&amount=10").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(keys(&body), ["amount", "base", "date", "rates"]);
+        assert_eq!(body["amount"], 10.0);
+        assert_eq!(body["base"], "EUR");
+        assert_eq!(body["date"], "2024-06-11");
+        assert_eq!(body["rates"], json!({ "GBP": 5.0, "USD": 12.5 }));
+
+        let (_, body) = get(rates("open.er-api.com"), "/latest?to=USD").await;
+        assert_eq!(body["amount"], 1.0);
+        assert_eq!(body["rates"], json!({ "USD": 1.25 }));
+    }
+
+    #[tokio::test]
+    async fn mock_rates_are_never_mirrored() {
+        let (status, body) = get(rates(MOCK_PROVIDER), "/v6/latest/EUR").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["error-type"], "upstream-unavailable");
+
+        let (status, body) = get(rates(MOCK_PROVIDER), "/latest").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(keys(&body), ["message"]);
+    }
+}
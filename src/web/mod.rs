@@ -1,18 +1,84 @@
+mod api;
+mod assets;
+mod auth;
+mod csrf;
+mod health;
+mod history;
+mod mirror;
+mod rate_limit;
 mod routes;
+mod security;
+mod server;
+mod stream;
 mod templates;
+mod tls;
 
-use axum::Router;
+use axum::{Router, middleware, routing::get};
 use std::sync::Arc;
 use tower_http::trace::TraceLayer;
 
+use crate::alerts::AlertStore;
+use crate::audit::AuditLog;
+use crate::cache::RateCache;
+use crate::store::SnapshotStore;
+use crate::webhooks::WebhookStore;
+use crate::format::Locale;
+
+pub use assets::has_htmx_sse;
+pub use rate_limit::{RateLimitConfig, RateLimiter};
+pub use security::SecurityConfig;
+#[cfg(unix)]
+pub use server::serve_unix;
+pub use server::serve_tcp;
+pub use tls::{TlsFiles, serve_tls};
+
 pub struct AppState {
-    pub api_key: String,
+    pub rates: Arc<RateCache>,
+    /// Locale used when a request's Accept-Language names no supported locale
+    pub locale: Option<Locale>,
+    /// Currency codes pinned at the top of the currency dropdowns
+    pub favourites: Vec<String>,
+    pub security: SecurityConfig,
+    pub rate_limiter: RateLimiter,
+    /// Conversion audit log, absent with `--no-db`
+    pub audit: Option<Arc<AuditLog>>,
+    /// Recent conversions per browser session
+    pub history: history::History,
+    /// Rate alert rules managed through `/api/alerts`, absent with `--no-db`
+    pub alerts: Option<Arc<AlertStore>>,
+    /// Webhook subscriptions managed through `/api/webhooks`, absent with `--no-db`
+    pub webhooks: Option<Arc<WebhookStore>>,
+    /// Rate snapshots for valuing portfolios at past dates, absent with `--no-db`
+    pub snapshots: Option<Arc<SnapshotStore>>,
+    /// Keep displayed conversion results updated over Server-Sent Events
+    pub live_results: bool,
+    /// Keys accepted for managing alerts and webhooks; none disables those endpoints
+    pub admin_keys: Vec<String>,
+    /// Let webhook subscriptions target loopback and private addresses
+    pub allow_private_webhooks: bool,
 }
 
-pub async fn create_app(api_key: String) -> Router {
-    let state = Arc::new(AppState { api_key });
+pub async fn create_app(state: Arc<AppState>) -> Router {
+    Router::new()
+        .merge(
+            routes::router(state.clone())
+                .layer(middleware::from_fn_with_state(state.clone(), history::session))
+                .layer(middleware::from_fn_with_state(state.clone(), csrf::verify))
+                .layer(middleware::from_fn_with_state(state.clone(), rate_limit::limit)),
+        )
+        .merge(api::router(state.clone()).layer(middleware::from_fn_with_state(state.clone(), rate_limit::limit_api)))
+        .route("/static/*path", get(assets::serve))
+        .merge(health::router(Arc::new(health::Health::new(state.rates.clone()))))
+        .route_layer(middleware::from_fn(health::track))
+        .layer(middleware::from_fn_with_state(state, security::security_headers))
+        .layer(TraceLayer::new_for_http().make_span_with(crate::telemetry::make_request_span))
+}
 
+/// Standalone rate mirror: upstream-compatible endpoints plus health and metrics.
+pub fn create_mirror_app(rates: Arc<RateCache>) -> Router {
     Router::new()
-        .merge(routes::router(state))
-        .layer(TraceLayer::new_for_http())
+        .merge(mirror::router(rates.clone()))
+        .merge(health::router(Arc::new(health::Health::new(rates))))
+        .route_layer(middleware::from_fn(health::track))
+        .layer(TraceLayer::new_for_http().make_span_with(crate::telemetry::make_request_span))
 }
@@ -0,0 +1,211 @@
+//! Per-client token-bucket rate limiting for the UI routes.
+//!
+//! Requests carrying a configured `X-API-Key` get a bucket per key; everyone
+//! else is bucketed by client IP. Unknown keys are ignored so they cannot be
+//! used to mint fresh buckets, and known keys are logged by their position in
+//! the configuration rather than their value.
+
+use axum::{
+    Json,
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use super::{AppState, templates};
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Drop idle buckets once this many clients are being tracked.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+#[derive(Clone)]
+pub struct RateLimitConfig {
+    /// Sustained requests per minute per client; 0 disables limiting
+    pub per_minute: u32,
+    /// Requests a client may make in a burst before being throttled
+    pub burst: u32,
+    /// Keys that get their own bucket instead of sharing their IP's
+    pub api_keys: Vec<String>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            per_minute: 60,
+            burst: 20,
+            api_keys: Vec::new(),
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn refill_per_second(&self) -> f64 {
+        f64::from(self.config.per_minute) / 60.0
+    }
+
+    /// Takes a token from `key`'s bucket, or returns how long until one is available.
+    fn acquire(&self, key: &str) -> Result<(), Duration> {
+        let capacity = f64::from(self.config.burst.max(1));
+        let refill = self.refill_per_second();
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if buckets.len() >= PRUNE_THRESHOLD {
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated_at).as_secs_f64() * refill < capacity
+            });
+        }
+
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket { tokens: capacity, updated_at: now });
+        bucket.tokens = (bucket.tokens + now.duration_since(bucket.updated_at).as_secs_f64() * refill).min(capacity);
+        bucket.updated_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / refill))
+        }
+    }
+
+    /// Bucket name for the request's client, which is safe to log.
+    fn client_key(&self, request: &Request) -> String {
+        if let Some(key) = request.headers().get(API_KEY_HEADER).and_then(|value| value.to_str().ok())
+            && let Some(index) = self.config.api_keys.iter().position(|known| known == key)
+        {
+            return format!("key#{}", index + 1);
+        }
+
+        match request.extensions().get::<ConnectInfo<SocketAddr>>() {
+            Some(ConnectInfo(addr)) => format!("ip:{}", addr.ip()),
+            None => "ip:unknown".to_string(),
+        }
+    }
+}
+
+/// Limits the UI routes, answering throttled requests with an error fragment.
+pub async fn limit(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    throttle(&state, request, next, |message| {
+        (StatusCode::TOO_MANY_REQUESTS, templates::render_error(message)).into_response()
+    })
+    .await
+}
+
+/// Limits the JSON API, answering throttled requests with a JSON error body.
+pub async fn limit_api(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    throttle(&state, request, next, |message| {
+        (StatusCode::TOO_MANY_REQUESTS, Json(json!({ "error": message }))).into_response()
+    })
+    .await
+}
+
+async fn throttle(state: &AppState, request: Request, next: Next, reject: impl FnOnce(String) -> Response) -> Response {
+    let limiter = &state.rate_limiter;
+    if limiter.config.per_minute == 0 {
+        return next.run(request).await;
+    }
+
+    let key = limiter.client_key(&request);
+    match limiter.acquire(&key) {
+        Ok(()) => next.run(request).await,
+        Err(wait) => {
+            let seconds = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+            tracing::warn!("Rate limit exceeded for {}", key);
+
+            let mut response = reject(format!(
+                "Too many requests. Please wait {} second{} and try again.",
+                seconds,
+                if seconds == 1 { "" } else { "s" }
+            ));
+            response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+            response
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::RateCache;
+    use axum::{
+        Router,
+        body::{Body, to_bytes},
+        middleware,
+        routing::get,
+    };
+    use tower::ServiceExt;
+
+    fn state() -> Arc<AppState> {
+        Arc::new(AppState {
+            rates: Arc::new(RateCache::new(String::new(), Duration::from_secs(600))),
+            locale: None,
+            favourites: Vec::new(),
+            security: Default::default(),
+            rate_limiter: RateLimiter::new(RateLimitConfig {
+                per_minute: 1,
+                burst: 1,
+                api_keys: vec!["first".to_string(), "second".to_string()],
+            }),
+            audit: None,
+            history: Default::default(),
+            alerts: None,
+            webhooks: None,
+            snapshots: None,
+            live_results: false,
+            admin_keys: Vec::new(),
+            allow_private_webhooks: false,
+        })
+    }
+
+    #[test]
+    fn api_keys_are_named_by_position() {
+        let limiter = &state().rate_limiter;
+        let request = Request::get("/").header(API_KEY_HEADER, "second").body(Body::empty()).unwrap();
+        assert_eq!(limiter.client_key(&request), "key#2");
+        let request = Request::get("/").header(API_KEY_HEADER, "unknown").body(Body::empty()).unwrap();
+        assert_eq!(limiter.client_key(&request), "ip:unknown");
+    }
+
+    #[tokio::test]
+    async fn throttled_api_requests_get_json() {
+        let state = state();
+        let app = Router::new()
+            .route("/api/alerts", get(|| async { "[]" }))
+            .layer(middleware::from_fn_with_state(state.clone(), limit_api));
+
+        let request = || Request::get("/api/alerts").body(Body::empty()).unwrap();
+        assert_eq!(app.clone().oneshot(request()).await.unwrap().status(), StatusCode::OK);
+        let response = app.oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.headers().contains_key(header::RETRY_AFTER));
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(body["error"].as_str().unwrap().starts_with("Too many requests"));
+    }
+}
@@ -1,207 +1,434 @@
-use axum::{extract::{State, Form}, response::Html, routing::{get, post}, Router};
+use axum::{extract::{ConnectInfo, Path, State, Form, Query}, http::{HeaderMap, StatusCode, header}, response::{Html, IntoResponse, Response, sse::{Event, KeepAlive, Sse}}, routing::{get, post}, Extension, Router};
+use futures_util::{StreamExt, future::ready};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, sync::Arc, time::{SystemTime, UNIX_EPOCH}};
-use chrono::{DateTime, Utc};
+use std::{convert::Infallible, net::SocketAddr, sync::Arc, time::{SystemTime, UNIX_EPOCH}};
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, Utc};
 
-use crate::ExchangeRates;
+use crate::{ExchangeRates, audit::AuditRecord, currency, expr, format::{self, Locale}, portfolio::{self, Valuation}};
 use super::AppState;
-use super::templates;
+use super::csrf::CsrfToken;
+use super::history::{self, SessionId, Submission};
+use super::security::CspNonce;
+use super::{stream, templates};
 
 #[derive(Deserialize)]
 pub struct ConversionForm {
-    amount: f64,
+    amount: String,
     from: String,
     to: String,
 }
 
-#[derive(Serialize)]
+/// Longest amount expression accepted from the form
+const MAX_AMOUNT_LENGTH: usize = 100;
+/// Longest holdings list accepted from the portfolio form
+pub const MAX_HOLDINGS_LENGTH: usize = 20_000;
+/// Longest currency search accepted from the list filter
+const MAX_SEARCH_LENGTH: usize = 50;
+
+impl ConversionForm {
+    /// Rejects malformed input before it reaches the providers or the templates.
+    fn validate(&self) -> Result<(), &'static str> {
+        if self.amount.trim().is_empty() || self.amount.len() > MAX_AMOUNT_LENGTH {
+            return Err("Amount must be between 1 and 100 characters");
+        }
+        if !currency::is_currency_code(&self.from) {
+            return Err("Source currency must be a three-letter code");
+        }
+        if !currency::is_currency_code(&self.to) {
+            return Err("Target currency must be a three-letter code");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Serialize)]
 pub struct ConversionResult {
     pub amount: String,
     pub from: String,
     pub to: String,
     pub result: String,
     pub rate: String,
+    pub inverse_rate: String,
     pub timestamp: String,
 }
 
-async fn fetch_exchange_rates(_api_key: &str, base: &str) -> anyhow::Result<ExchangeRates> {
-    let client = reqwest::Client::new();
-    
-    // Try the free API from ExchangeRate-API first (no key required)
-    let free_url = format!("https://open.er-api.com/v6/latest/{}", base);
-    let free_response = client.get(&free_url).send().await;
-    
-    if let Ok(response) = free_response {
-        if response.status().is_success() {
-            // The free API has a different response format, so we need to parse it differently
-            let response_text = response.text().await.unwrap_or_default();
-            
-            // Try to parse the response
-            if let Ok(value) = serde_json::from_str::<serde_json::Value>(&response_text) {
-                if let Some(rates_obj) = value.get("rates") {
-                    if let Some(rates_map) = rates_obj.as_object() {
-                        let mut rates = HashMap::new();
-                        
-                        for (currency, rate) in rates_map {
-                            if let Some(rate_val) = rate.as_f64() {
-                                rates.insert(currency.clone(), rate_val);
-                            }
-                        }
-                        
-                        println!("Successfully fetched rates from open.er-api.com");
-                        return Ok(ExchangeRates {
-                            success: true,
-                            timestamp: value.get("time_last_update_unix").and_then(|v| v.as_u64()),
-                            base: Some(base.to_string()),
-                            date: value.get("time_last_update_utc").and_then(|v| v.as_str()).map(|s| s.to_string()),
-                            rates,
-                        });
-                    }
-                }
-            } else {
-                println!("Error parsing free API response: {}", anyhow::anyhow!("Invalid JSON"));
-            }
+#[derive(Deserialize)]
+pub struct PortfolioForm {
+    holdings: String,
+    currency: String,
+    /// Comparison date (YYYY-MM-DD), empty for none
+    #[serde(default)]
+    compare: String,
+}
+
+/// A change in value, formatted with its sign.
+pub struct Change {
+    pub amount: String,
+    pub loss: bool,
+}
+
+pub struct PortfolioRow {
+    pub label: String,
+    pub amount: String,
+    pub value: String,
+    pub weight: String,
+    pub gain: Option<Change>,
+}
+
+pub struct PortfolioComparison {
+    /// Which rates the holdings were compared against
+    pub rates: String,
+    pub total: String,
+    pub gain: Change,
+    /// Gain relative to the previous total, e.g. " (+1.25%)"
+    pub ratio: String,
+}
+
+pub struct PortfolioView {
+    pub currency: String,
+    pub rates: String,
+    pub rows: Vec<PortfolioRow>,
+    pub total: String,
+    pub comparison: Option<PortfolioComparison>,
+}
+
+#[derive(Deserialize)]
+pub struct CurrenciesQuery {
+    base: Option<String>,
+    q: Option<String>,
+}
+
+/// Filter for the currency pickers. The picker sends its current value under
+/// its own field name, so the selection survives when it still matches.
+#[derive(Deserialize)]
+pub struct CurrencyOptionsQuery {
+    #[serde(default)]
+    q: String,
+    #[serde(alias = "from", alias = "to")]
+    selected: Option<String>,
+}
+
+pub struct CurrencyEntry {
+    pub code: String,
+    pub name: String,
+    pub rate: String,
+}
+
+pub struct CurrencyOption {
+    pub code: String,
+    pub name: String,
+}
+
+async fn index(
+    State(state): State<Arc<AppState>>,
+    Extension(nonce): Extension<CspNonce>,
+    Extension(csrf_token): Extension<CsrfToken>,
+) -> Html<String> {
+    match state.rates.get("EUR").await {
+        Ok(rates) => {
+            let (favourites, others) = currency_options(&rates, &state.favourites, "");
+            templates::render_index(&favourites, &others, &nonce.0, &csrf_token.0, state.live_results)
         }
+        Err(e) => templates::render_error(e.to_string()),
     }
-    
-    // Try another free API from fawazahmed0/currency-api (GitHub)
-    let fallback_url = format!("https://cdn.jsdelivr.net/gh/fawazahmed0/currency-api@1/latest/currencies/{}.json", base.to_lowercase());
-    let fallback_response = client.get(&fallback_url).send().await;
-    
-    if let Ok(response) = fallback_response {
-        if response.status().is_success() {
-            // This API has yet another format
-            let response_text = response.text().await.unwrap_or_default();
-            
-            if let Ok(value) = serde_json::from_str::<serde_json::Value>(&response_text) {
-                if let Some(rates_obj) = value.get(base.to_lowercase()) {
-                    if let Some(rates_map) = rates_obj.as_object() {
-                        let mut rates = HashMap::new();
-                        
-                        for (currency, rate) in rates_map {
-                            if let Some(rate_val) = rate.as_f64() {
-                                rates.insert(currency.to_uppercase(), rate_val);
-                            }
-                        }
-                        
-                        println!("Successfully fetched rates from fawazahmed0/currency-api");
-                        return Ok(ExchangeRates {
-                            success: true,
-                            timestamp: Some(SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()),
-                            base: Some(base.to_string()),
-                            date: Some(chrono::Utc::now().format("%Y-%m-%d").to_string()),
-                            rates,
-                        });
-                    }
-                }
-            } else {
-                println!("Error parsing API response: {}", anyhow::anyhow!("Invalid JSON"));
-            }
+}
+
+async fn portfolio_page(
+    State(state): State<Arc<AppState>>,
+    Extension(nonce): Extension<CspNonce>,
+    Extension(csrf_token): Extension<CsrfToken>,
+) -> Html<String> {
+    match state.rates.get("EUR").await {
+        Ok(rates) => {
+            let (favourites, others) = currency_options(&rates, &state.favourites, "");
+            templates::render_portfolio(&favourites, &others, &nonce.0, &csrf_token.0)
         }
+        Err(e) => templates::render_error(e.to_string()),
     }
-    
-    // Try the Frankfurter API (another free option)
-    let frankfurter_url = format!("https://api.frankfurter.app/latest?from={}", base);
-    let frankfurter_response = client.get(&frankfurter_url).send().await;
-    
-    if let Ok(response) = frankfurter_response {
-        if response.status().is_success() {
-            let response_text = response.text().await.unwrap_or_default();
-            
-            if let Ok(value) = serde_json::from_str::<serde_json::Value>(&response_text) {
-                if let Some(rates_obj) = value.get("rates") {
-                    if let Some(rates_map) = rates_obj.as_object() {
-                        let mut rates = HashMap::new();
-                        
-                        for (currency, rate) in rates_map {
-                            if let Some(rate_val) = rate.as_f64() {
-                                rates.insert(currency.clone(), rate_val);
-                            }
-                        }
-                        
-                        println!("Successfully fetched rates from api.frankfurter.app");
-                        return Ok(ExchangeRates {
-                            success: true,
-                            timestamp: Some(SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()),
-                            base: Some(base.to_string()),
-                            date: value.get("date").and_then(|v| v.as_str()).map(|s| s.to_string()),
-                            rates,
-                        });
-                    }
-                }
-            } else {
-                println!("Error parsing Frankfurter API response: {}", anyhow::anyhow!("Invalid JSON"));
+}
+
+/// Values the submitted holdings at the cached rates for the reporting
+/// currency, and at the rates of the comparison date when one is given.
+async fn value_portfolio(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Form(form): Form<PortfolioForm>,
+) -> Html<String> {
+    if form.holdings.len() > MAX_HOLDINGS_LENGTH {
+        return templates::render_error("The holdings list is too long".to_string());
+    }
+    if !currency::is_currency_code(&form.currency) {
+        return templates::render_error("Reporting currency must be a three-letter code".to_string());
+    }
+    let compare = match form.compare.trim() {
+        "" => None,
+        date => match date.parse::<NaiveDate>() {
+            Ok(date) if date < Utc::now().date_naive() => Some(date),
+            Ok(_) => return templates::render_error("The comparison date must be in the past".to_string()),
+            Err(_) => return templates::render_error("The comparison date must be YYYY-MM-DD".to_string()),
+        },
+    };
+
+    let locale = request_locale(&state, &headers);
+    match portfolio_valuation(&state, &form.holdings, &form.currency.to_uppercase(), compare).await {
+        Ok(valuation) => templates::render_portfolio_result(portfolio_view(&valuation, &locale)),
+        Err(e) => templates::render_error(format!("{:#}", e)),
+    }
+}
+
+#[tracing::instrument(name = "portfolio_valuation", skip(state, holdings), err(Display, level = "warn"))]
+async fn portfolio_valuation(
+    state: &AppState,
+    holdings: &str,
+    currency: &str,
+    compare: Option<NaiveDate>,
+) -> anyhow::Result<Valuation> {
+    let holdings = portfolio::from_csv(holdings)?;
+    let rates = state.rates.get(currency).await?;
+    let previous = match compare {
+        Some(date) => Some(crate::historical_rates(state.snapshots.as_deref(), currency, date).await?),
+        None => None,
+    };
+    Valuation::new(holdings, currency, &rates, previous.as_ref())
+}
+
+fn change(amount: f64, currency: &str, locale: &Locale) -> Change {
+    let amount = format::signed_money(amount, currency, locale);
+    Change { loss: amount.starts_with('-'), amount }
+}
+
+fn portfolio_view(valuation: &Valuation, locale: &Locale) -> PortfolioView {
+    let currency = &valuation.currency;
+    let rows = valuation
+        .positions
+        .iter()
+        .map(|position| {
+            let holding = &position.holding;
+            PortfolioRow {
+                label: holding.label.clone().unwrap_or_else(|| holding.currency.clone()),
+                amount: format::money(holding.amount, &holding.currency, locale),
+                value: format::money(position.value, currency, locale),
+                weight: format!("{:.1}%", position.weight * 100.0),
+                gain: position.gain().map(|gain| change(gain, currency, locale)),
             }
-        }
+        })
+        .collect();
+    let comparison = valuation.previous.as_ref().zip(valuation.gain()).map(|(previous, gain)| PortfolioComparison {
+        rates: crate::describe_rates(&previous.rates),
+        total: format::money(previous.total, currency, locale),
+        gain: change(gain, currency, locale),
+        ratio: valuation.gain_ratio().map(|ratio| format!(" ({:+.2}%)", ratio * 100.0)).unwrap_or_default(),
+    });
+
+    PortfolioView {
+        currency: currency.clone(),
+        rates: crate::describe_rates(&valuation.rates),
+        rows,
+        total: format::money(valuation.total, currency, locale),
+        comparison,
     }
-    
-    // We no longer need to use the exchangerate.host API since we're using free APIs
-    
-    // If all APIs fail, return a mock response for demonstration purposes
-    // In a real app, you'd want to handle this differently
-    let mut mock_rates = HashMap::new();
-    mock_rates.insert("USD".to_string(), 1.08);
-    mock_rates.insert("EUR".to_string(), 1.0);
-    mock_rates.insert("GBP".to_string(), 0.85);
-    mock_rates.insert("JPY".to_string(), 160.0);
-    mock_rates.insert("CAD".to_string(), 1.47);
-    mock_rates.insert("AUD".to_string(), 1.63);
-    mock_rates.insert("CHF".to_string(), 0.97);
-    mock_rates.insert("CNY".to_string(), 7.8);
-    mock_rates.insert("PLN".to_string(), 4.26);
-    mock_rates.insert("UAH".to_string(), 42.5);
-    
-    // Convert rates if base is not EUR
-    if base != "EUR" {
-        let base_rate = *mock_rates.get(base).unwrap_or(&1.0);
-        let mut converted_rates = HashMap::new();
-        for (currency, rate) in mock_rates.iter() {
-            converted_rates.insert(currency.clone(), rate / base_rate);
-        }
-        mock_rates = converted_rates;
+}
+
+/// Splits the currencies in the rate table whose code or name matches `search`
+/// into pinned favourites and the rest, sorted by code.
+fn currency_options(
+    rates: &ExchangeRates,
+    favourites: &[String],
+    search: &str,
+) -> (Vec<CurrencyOption>, Vec<CurrencyOption>) {
+    let name = |code: &str| currency::find(code).map(|currency| currency.name).unwrap_or_default();
+    let option = |code: &str| CurrencyOption { code: code.to_string(), name: name(code).to_string() };
+
+    let mut codes: Vec<&str> = rates.rates.keys().map(String::as_str).collect();
+    if let Some(base) = rates.base.as_deref()
+        && !codes.contains(&base)
+    {
+        codes.push(base);
     }
-    
-    println!("Using mock exchange rates for demonstration");
-    
-    Ok(ExchangeRates {
-        success: true,
-        timestamp: Some(SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()),
-        base: Some(base.to_string()),
-        date: Some(chrono::Utc::now().format("%Y-%m-%d").to_string()),
-        rates: mock_rates,
-    })
+    codes.retain(|code| matches_search(code, name(code), search));
+    codes.sort();
+
+    let pinned = favourites
+        .iter()
+        .filter(|code| codes.contains(&code.as_str()))
+        .map(|code| option(code))
+        .collect();
+    let others = codes
+        .into_iter()
+        .filter(|code| !favourites.iter().any(|favourite| favourite == code))
+        .map(option)
+        .collect();
+
+    (pinned, others)
 }
 
-async fn index() -> Html<String> {
-    templates::render_index()
+/// Whether a currency's code or name contains `search`, ignoring case.
+fn matches_search(code: &str, name: &str, search: &str) -> bool {
+    let search = search.trim().to_lowercase();
+    search.is_empty() || code.to_lowercase().contains(&search) || name.to_lowercase().contains(&search)
+}
+
+/// Locale for a request: Accept-Language first, then the configured default.
+fn request_locale(state: &AppState, headers: &HeaderMap) -> Locale {
+    headers
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(Locale::from_accept_language)
+        .or(state.locale)
+        .unwrap_or_default()
 }
 
 async fn convert(
     State(state): State<Arc<AppState>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    session: Option<Extension<SessionId>>,
+    headers: HeaderMap,
     Form(form): Form<ConversionForm>,
 ) -> Html<String> {
+    if let Err(message) = form.validate() {
+        return templates::render_error(message.to_string());
+    }
+
+    let submission = Submission {
+        amount: form.amount,
+        from: form.from.to_uppercase(),
+        to: form.to.to_uppercase(),
+    };
+    convert_and_record(&state, connect_info, session, &headers, submission).await
+}
+
+/// Re-runs a conversion from the session's history at current rates.
+async fn rerun(
+    State(state): State<Arc<AppState>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    session: Option<Extension<SessionId>>,
+    headers: HeaderMap,
+    Path(id): Path<u64>,
+) -> Html<String> {
+    let submission = session
+        .as_ref()
+        .and_then(|Extension(session)| state.history.submission(&session.0, id));
+    match submission {
+        Some(submission) => convert_and_record(&state, connect_info, session, &headers, submission).await,
+        None => templates::render_error("This conversion is no longer in your history".to_string()),
+    }
+}
+
+/// Converts `submission`, adding it to the session's history. The response
+/// also replaces the history panel out of band.
+async fn convert_and_record(
+    state: &AppState,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    session: Option<Extension<SessionId>>,
+    headers: &HeaderMap,
+    submission: Submission,
+) -> Html<String> {
+    // Unix socket connections carry no peer address
+    let caller = match connect_info {
+        Some(ConnectInfo(addr)) => format!("web:{}", addr.ip()),
+        None => "web:unknown".to_string(),
+    };
+    let locale = request_locale(state, headers);
     let result = convert_currency(
-        &state.api_key,
-        form.amount,
-        &form.from,
-        &form.to,
+        state,
+        caller,
+        &submission.amount,
+        &submission.from,
+        &submission.to,
+        &locale,
     ).await;
 
-    match result {
-        Ok(conversion) => templates::render_conversion_result(conversion),
-        Err(e) => templates::render_error(e.to_string()),
-    }
+    let (conversion, record) = match result {
+        Ok(result) => result,
+        Err(e) => return templates::render_error(e.to_string()),
+    };
+    let live_url = state.live_results.then(|| live_url(&submission));
+    let Some(Extension(session)) = session else {
+        return templates::render_result_panel(conversion, live_url);
+    };
+
+    state.history.push(&session.0, submission, conversion.clone(), record);
+    let Html(mut content) = templates::render_result_panel(conversion, live_url);
+    let Html(panel) = state.history.with_entries(&session.0, |entries| templates::render_history(entries, true));
+    content.push_str(&panel);
+    Html(content)
+}
+
+async fn history_panel(State(state): State<Arc<AppState>>, Extension(session): Extension<SessionId>) -> Html<String> {
+    state.history.with_entries(&session.0, |entries| templates::render_history(entries, false))
 }
 
+async fn history_csv(State(state): State<Arc<AppState>>, Extension(session): Extension<SessionId>) -> Response {
+    let csv = state.history.with_entries(&session.0, history::to_csv);
+    (
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (header::CONTENT_DISPOSITION, "attachment; filename=\"conversion-history.csv\""),
+        ],
+        csv,
+    )
+        .into_response()
+}
+
+async fn clear_history(State(state): State<Arc<AppState>>, Extension(session): Extension<SessionId>) -> Html<String> {
+    state.history.clear(&session.0);
+    templates::render_history(&[], false)
+}
+
+#[tracing::instrument(name = "conversion", skip(state, locale), err(Display, level = "warn"))]
 async fn convert_currency(
-    api_key: &str,
+    state: &AppState,
+    caller: String,
+    amount: &str,
+    from: &str,
+    to: &str,
+    locale: &Locale,
+) -> anyhow::Result<(ConversionResult, AuditRecord)> {
+    let expression = expr::parse(amount, locale)?;
+    let rates = state.rates.get(from).await?;
+    let conversion = evaluate(&expression, &rates, from, to, locale)?;
+
+    let record = AuditRecord::new(
+        caller,
+        amount,
+        &conversion.result.from,
+        &conversion.result.to,
+        conversion.amount,
+        conversion.rate,
+        &rates,
+    )
+    .with_legs(conversion.legs);
+    if let Some(audit) = &state.audit {
+        audit.append(record.clone()).context("Failed to record conversion in the audit log")?;
+    }
+    Ok((conversion.result, record))
+}
+
+/// A conversion formatted for display, with the exact values behind it.
+struct Evaluated {
+    result: ConversionResult,
+    /// Evaluated amount in `result.from`
     amount: f64,
+    rate: f64,
+    legs: Vec<expr::Leg>,
+}
+
+/// Evaluates `expression` against `rates`, converting into its own target
+/// currency when it names one and `to` otherwise.
+fn evaluate(
+    expression: &expr::Expression,
+    rates: &ExchangeRates,
     from: &str,
     to: &str,
-) -> anyhow::Result<ConversionResult> {
-    let rates = fetch_exchange_rates(api_key, from).await?;
-    
-    let rate = rates.rates.get(to).ok_or_else(|| anyhow::anyhow!("Currency {} not found", to))?;
+    locale: &Locale,
+) -> anyhow::Result<Evaluated> {
+    let to = expression.target.as_deref().unwrap_or(to);
+    let value = expression.evaluate(to, rates)?;
+    let from = value.currency.as_deref().unwrap_or(from);
+    let rate = expr::cross_rate(rates, from, to)?;
+    let amount = value.amount;
+    let legs = value.legs;
     let converted = amount * rate;
     
     // Format the timestamp
@@ -210,27 +437,301 @@ async fn convert_currency(
     let dt = DateTime::<Utc>::from_timestamp(timestamp as i64, 0).unwrap();
     let formatted_time = dt.format("%Y-%m-%d %H:%M:%S UTC").to_string();
     
-    // Format numbers with 2 decimal places
-    let formatted_amount = format!("{:.2}", amount);
-    let formatted_result = format!("{:.2}", (converted * 100.0).round() / 100.0);
-    let formatted_rate = format!("{:.2}", (rate * 100.0).round() / 100.0);
+    // Format amounts for the locale, rates with significant figures
+    let formatted_amount = format::money(amount, from, locale);
+    let formatted_result = format::money(converted, to, locale);
+    let formatted_rate = format::significant(rate, format::RATE_SIGNIFICANT_FIGURES);
+    let formatted_inverse_rate = format::significant(1.0 / rate, format::RATE_SIGNIFICANT_FIGURES);
     
-    Ok(ConversionResult {
+    let result = ConversionResult {
         amount: formatted_amount,
         from: from.to_string(),
         to: to.to_string(),
         result: formatted_result,
         rate: formatted_rate,
+        inverse_rate: formatted_inverse_rate,
         timestamp: formatted_time,
-    })
+    };
+    Ok(Evaluated { result, amount, rate, legs })
+}
+
+/// Re-renders a conversion as `result` events whenever the rates for its
+/// source currency are refreshed. Live updates only change what is displayed:
+/// they are not audited or added to the history.
+async fn live_result(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(form): Query<ConversionForm>,
+) -> Response {
+    if !state.live_results {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    if let Err(message) = form.validate() {
+        return (StatusCode::BAD_REQUEST, message).into_response();
+    }
+    let locale = request_locale(&state, &headers);
+    let expression = match expr::parse(&form.amount, &locale) {
+        Ok(expression) => expression,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+
+    let (from, to) = (form.from.to_uppercase(), form.to.to_uppercase());
+    let mut last = None;
+    let events = stream::rate_updates(state.rates.clone(), from.clone()).filter_map(move |rates| {
+        // The timestamp changes on every render, so compare the rate itself
+        let event = evaluate(&expression, &rates, &from, &to, &locale).ok().and_then(|conversion| {
+            let changed = last.replace(conversion.rate) != Some(conversion.rate);
+            changed.then(|| {
+                let Html(content) = templates::render_conversion_result(conversion.result);
+                Ok::<_, Infallible>(Event::default().event("result").data(content))
+            })
+        });
+        ready(event)
+    });
+
+    Sse::new(events).keep_alive(KeepAlive::default()).into_response()
+}
+
+/// URL of the [`live_result`] stream for `submission`.
+fn live_url(submission: &Submission) -> String {
+    let query = serde_urlencoded::to_string([
+        ("amount", &submission.amount),
+        ("from", &submission.from),
+        ("to", &submission.to),
+    ])
+    .unwrap_or_default();
+    format!("/convert/live?{}", query)
+}
+
+async fn currencies(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CurrenciesQuery>,
+) -> Html<String> {
+    let base = query
+        .base
+        .filter(|base| !base.trim().is_empty())
+        .map(|base| base.trim().to_uppercase())
+        .unwrap_or_else(|| "EUR".to_string());
+    let search = query.q.unwrap_or_default();
+
+    if !currency::is_currency_code(&base) {
+        return templates::render_error("Base currency must be a three-letter code".to_string());
+    }
+    if search.len() > MAX_SEARCH_LENGTH {
+        return templates::render_error("Search is too long".to_string());
+    }
+
+    match state.rates.get(&base).await {
+        Ok(rates) => templates::render_currencies_list(&base, list_currencies(&rates, &search)),
+        Err(e) => templates::render_error(e.to_string()),
+    }
+}
+
+/// Options for a currency picker, narrowed to the currencies matching the search.
+async fn currency_picker_options(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CurrencyOptionsQuery>,
+) -> Html<String> {
+    if query.q.len() > MAX_SEARCH_LENGTH {
+        return templates::render_error("Search is too long".to_string());
+    }
+    let selected = query.selected.filter(|code| currency::is_currency_code(code)).unwrap_or_default();
+
+    match state.rates.get("EUR").await {
+        Ok(rates) => {
+            let (favourites, others) = currency_options(&rates, &state.favourites, &query.q);
+            templates::render_currency_options(&favourites, &others, &selected)
+        }
+        Err(e) => templates::render_error(e.to_string()),
+    }
 }
 
-// Removed unused list_currencies and get_currencies functions
+/// Merges the live rate table with ISO names, keeping entries whose code or name matches `search`.
+fn list_currencies(rates: &ExchangeRates, search: &str) -> Vec<CurrencyEntry> {
+    let mut codes: Vec<&String> = rates.rates.keys().collect();
+    codes.sort();
+
+    codes
+        .into_iter()
+        .filter_map(|code| {
+            let name = currency::find(code).map(|currency| currency.name).unwrap_or_default();
+            matches_search(code, name, search).then(|| CurrencyEntry {
+                code: code.clone(),
+                name: name.to_string(),
+                rate: format::significant(rates.rates[code], format::RATE_SIGNIFICANT_FIGURES),
+            })
+        })
+        .collect()
+}
 
 pub fn router(state: Arc<AppState>) -> Router {
     Router::new()
         .route("/", get(index))
         .route("/convert", post(convert))
-        // Removed the '/currencies' route as we no longer need it
+        .route("/convert/live", get(live_result))
+        .route("/currencies", get(currencies))
+        .route("/currency-options", get(currency_picker_options))
+        .route("/portfolio", get(portfolio_page).post(value_portfolio))
+        .route("/history", get(history_panel))
+        .route("/history.csv", get(history_csv))
+        .route("/history/clear", post(clear_history))
+        .route("/history/:id/rerun", post(rerun))
         .with_state(state)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cache::RateCache, web::RateLimiter};
+    use axum::{
+        body::{Body, to_bytes},
+        http::Request,
+    };
+    use std::{collections::HashMap, time::Duration};
+    use tower::ServiceExt;
+
+    const PAYLOAD: &str = "<script>alert(1)</script>";
+
+    fn app() -> Router {
+        app_with(Arc::new(RateCache::new(String::new(), Duration::from_secs(600))))
+    }
+
+    fn app_with(rates: Arc<RateCache>) -> Router {
+        router(Arc::new(AppState {
+            rates,
+            locale: None,
+            favourites: Vec::new(),
+            security: Default::default(),
+            rate_limiter: RateLimiter::new(Default::default()),
+            audit: None,
+            history: Default::default(),
+            alerts: None,
+            webhooks: None,
+            snapshots: None,
+            live_results: false,
+            admin_keys: Vec::new(),
+            allow_private_webhooks: false,
+        }))
+    }
+
+    async fn send(request: Request<Body>) -> String {
+        send_to(app(), request).await
+    }
+
+    async fn send_to(app: Router, request: Request<Body>) -> String {
+        let response = app.oneshot(request).await.unwrap();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    async fn post_convert(amount: &str, from: &str, to: &str) -> String {
+        let body = format!("amount={}&from={}&to={}", urlencode(amount), urlencode(from), urlencode(to));
+
+        send(
+            Request::post("/convert")
+                .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .body(Body::from(body))
+                .unwrap(),
+        )
+        .await
+    }
+
+    fn urlencode(value: &str) -> String {
+        value.bytes().map(|b| format!("%{:02X}", b)).collect()
+    }
+
+    #[tokio::test]
+    async fn script_in_target_currency_is_rejected_and_not_echoed() {
+        let body = post_convert("100", "USD", PAYLOAD).await;
+        assert!(body.contains("Target currency must be a three-letter code"));
+        assert!(!body.contains("<script>"));
+    }
+
+    #[tokio::test]
+    async fn script_in_source_currency_is_rejected_and_not_echoed() {
+        let body = post_convert("100", PAYLOAD, "EUR").await;
+        assert!(body.contains("Source currency must be a three-letter code"));
+        assert!(!body.contains("<script>"));
+    }
+
+    #[tokio::test]
+    async fn script_in_amount_is_escaped_in_error() {
+        let body = post_convert(PAYLOAD, "USD", "EUR").await;
+        assert!(!body.contains("<script>"));
+        assert!(body.contains("&lt;"));
+    }
+
+    #[tokio::test]
+    async fn script_in_currency_search_base_is_rejected() {
+        let uri = format!("/currencies?base={}", urlencode(PAYLOAD));
+        let body = send(Request::get(uri).body(Body::empty()).unwrap()).await;
+        assert!(body.contains("Base currency must be a three-letter code"));
+        assert!(!body.contains("<script>"));
+    }
+
+    #[tokio::test]
+    async fn picker_search_by_name_narrows_the_options() {
+        let cache = Arc::new(RateCache::new(String::new(), Duration::from_secs(600)));
+        cache
+            .insert(ExchangeRates {
+                success: true,
+                timestamp: None,
+                base: Some("EUR".to_string()),
+                date: None,
+                rates: HashMap::from([("USD".to_string(), 1.1), ("PLN".to_string(), 4.3), ("JPY".to_string(), 160.0)]),
+                provider: None,
+                fetched_at: None,
+            })
+            .await;
+        let options = |uri: &str| send_to(app_with(cache.clone()), Request::get(uri).body(Body::empty()).unwrap());
+
+        let all = options("/currency-options?q=&from=USD").await;
+        for code in ["EUR", "JPY", "PLN"] {
+            assert!(all.contains(&format!(r#"value="{}""#, code)), "{} missing from {}", code, all);
+        }
+        assert!(all.contains(r#"value="USD" selected"#));
+
+        let polish = options("/currency-options?q=polish&to=USD").await;
+        assert!(polish.contains(r#"value="PLN""#));
+        for code in ["EUR", "JPY", "USD"] {
+            assert!(!polish.contains(&format!(r#"value="{}""#, code)), "{} should be filtered out", code);
+        }
+
+        assert!(options("/currency-options?q=nothing").await.contains("No currencies match"));
+    }
+
+    #[tokio::test]
+    async fn script_in_portfolio_holdings_is_rejected_and_escaped() {
+        let body = format!("holdings={}&currency=EUR&compare=", urlencode(&format!("{},100", PAYLOAD)));
+        let body = send(
+            Request::post("/portfolio")
+                .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .body(Body::from(body))
+                .unwrap(),
+        )
+        .await;
+        assert!(body.contains("is not a three-letter currency code"));
+        assert!(!body.contains("<script>"));
+    }
+
+    #[test]
+    fn error_messages_are_escaped() {
+        let Html(body) = templates::render_error(format!("Currency {} not found", PAYLOAD));
+        assert!(body.contains("Currency &lt;script&gt;alert(1)&lt;/script&gt; not found"));
+        assert!(!body.contains("<script>"));
+    }
+
+    #[test]
+    fn conversion_results_are_escaped() {
+        let Html(body) = templates::render_conversion_result(ConversionResult {
+            amount: PAYLOAD.to_string(),
+            from: PAYLOAD.to_string(),
+            to: PAYLOAD.to_string(),
+            result: PAYLOAD.to_string(),
+            rate: PAYLOAD.to_string(),
+            inverse_rate: PAYLOAD.to_string(),
+            timestamp: PAYLOAD.to_string(),
+        });
+        assert!(!body.contains("<script>"));
+    }
+}
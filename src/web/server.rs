@@ -0,0 +1,165 @@
+//! Listeners and graceful shutdown for the web server.
+//!
+//! Both listeners stop accepting on SIGINT/SIGTERM and wait for in-flight
+//! requests to finish before returning, so callers can flush state afterwards.
+//! Long-lived responses (event streams) end once [`shutting_down`] resolves,
+//! otherwise they would hold the shutdown open indefinitely.
+
+use anyhow::{Context, Result, bail};
+use axum::Router;
+use std::{net::SocketAddr, sync::LazyLock};
+use tokio::{net::TcpListener, sync::watch};
+
+static SHUTTING_DOWN: LazyLock<watch::Sender<bool>> = LazyLock::new(|| watch::Sender::new(false));
+
+/// Resolves once a shutdown signal has been received.
+pub async fn shutting_down() {
+    let mut receiver = SHUTTING_DOWN.subscribe();
+    let _ = receiver.wait_for(|shutting_down| *shutting_down).await;
+}
+
+/// Resolves when the process receives Ctrl+C or SIGTERM.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for Ctrl+C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    SHUTTING_DOWN.send_replace(true);
+    tracing::info!("Shutting down, waiting for in-flight requests");
+}
+
+pub async fn serve_tcp(host: &str, port: u16, app: Router) -> Result<()> {
+    let listener = TcpListener::bind((host, port))
+        .await
+        .with_context(|| format!("Failed to bind to address {}:{}", host, port))?;
+    tracing::info!("Server started successfully on http://{}", listener.local_addr()?);
+
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(shutdown_signal())
+        .await
+        .context("Web server failed")
+}
+
+/// Removes the socket file at `path` if it was left behind by a previous run,
+/// failing when another process is still accepting connections on it.
+#[cfg(unix)]
+async fn remove_stale_socket(path: &std::path::Path) -> Result<()> {
+    use std::{io::ErrorKind, os::unix::fs::FileTypeExt};
+
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return Ok(());
+    };
+    if !metadata.file_type().is_socket() {
+        return Ok(());
+    }
+    match tokio::net::UnixStream::connect(path).await {
+        Ok(_) => bail!("Unix socket {} is already in use", path.display()),
+        Err(e) if e.kind() == ErrorKind::ConnectionRefused => {
+            std::fs::remove_file(path).with_context(|| format!("Failed to remove stale socket {}", path.display()))
+        }
+        Err(e) => Err(e).with_context(|| format!("Failed to check whether {} is in use", path.display())),
+    }
+}
+
+/// Serves `app` on a Unix domain socket, replacing a stale socket file left by
+/// a previous run. Requests carry no client address, so rate limiting treats
+/// them as a single client unless they present an API key.
+#[cfg(unix)]
+pub async fn serve_unix(path: &std::path::Path, app: Router) -> Result<()> {
+    use axum::body::Body;
+    use hyper::body::Incoming;
+    use hyper_util::{
+        rt::{TokioExecutor, TokioIo},
+        server::{conn::auto, graceful::GracefulShutdown},
+        service::TowerToHyperService,
+    };
+    use tokio::net::UnixListener;
+    use tower::ServiceExt;
+
+    remove_stale_socket(path).await?;
+
+    let listener =
+        UnixListener::bind(path).with_context(|| format!("Failed to bind to unix socket {}", path.display()))?;
+    tracing::info!("Server started successfully on unix:{}", path.display());
+
+    let builder = auto::Builder::new(TokioExecutor::new());
+    let graceful = GracefulShutdown::new();
+    let mut shutdown = std::pin::pin!(shutdown_signal());
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let stream = match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        tracing::error!("Failed to accept connection: {}", e);
+                        continue;
+                    }
+                };
+
+                let service = TowerToHyperService::new(
+                    app.clone().map_request(|request: hyper::Request<Incoming>| request.map(Body::new)),
+                );
+                let connection = builder.serve_connection_with_upgrades(TokioIo::new(stream), service).into_owned();
+                let connection = graceful.watch(connection);
+                tokio::spawn(async move {
+                    if let Err(e) = connection.await {
+                        tracing::debug!("Connection error: {}", e);
+                    }
+                });
+            }
+            _ = &mut shutdown => break,
+        }
+    }
+
+    drop(listener);
+    graceful.shutdown().await;
+    let _ = std::fs::remove_file(path);
+
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn only_stale_sockets_are_replaced() {
+        let path = std::env::temp_dir().join(format!("currency-converter-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+        let error = remove_stale_socket(&path).await.unwrap_err();
+        assert!(error.to_string().contains("already in use"), "{:#}", error);
+        assert!(path.exists());
+
+        // Dropping the listener leaves the file behind, as a crashed server would
+        drop(listener);
+        remove_stale_socket(&path).await.unwrap();
+        assert!(!path.exists());
+    }
+}
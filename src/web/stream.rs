@@ -0,0 +1,72 @@
+//! Live rate updates for Server-Sent Events responses.
+//!
+//! Streams follow the cache's broadcast of freshly fetched tables, so they
+//! move as often as the refresher (or any cache miss) fetches rates; nothing
+//! is polled per connection. They end when the server shuts down.
+
+use futures_util::{Stream, StreamExt, future::ready, stream};
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::cache::RateCache;
+use crate::{ExchangeRates, MOCK_PROVIDER};
+
+/// The cached table for `base`, then every table fetched for it afterwards.
+/// Mock data is skipped, so a stream stays quiet while the providers are down.
+pub fn rate_updates(cache: Arc<RateCache>, base: String) -> impl Stream<Item = Arc<ExchangeRates>> + Send + 'static {
+    // Subscribe before reading the current table so no fetch in between is missed
+    let updates = cache.subscribe();
+    let current = {
+        let base = base.clone();
+        stream::once(async move { cache.get(&base).await.ok() }).filter_map(ready)
+    };
+    let fetched = stream::unfold(updates, |mut updates| async move {
+        loop {
+            match updates.recv().await {
+                Ok(rates) => return Some((rates, updates)),
+                // Only the newest table matters to a stream that fell behind
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    current
+        .chain(fetched)
+        .filter(move |rates| {
+            ready(rates.base.as_deref() == Some(base.as_str()) && rates.provider.as_deref() != Some(MOCK_PROVIDER))
+        })
+        .take_until(super::server::shutting_down())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{collections::HashMap, time::Duration};
+
+    fn rates(provider: &str, base: &str, usd: f64) -> ExchangeRates {
+        ExchangeRates {
+            success: true,
+            timestamp: Some(1_718_000_000),
+            base: Some(base.to_string()),
+            date: None,
+            rates: HashMap::from([("USD".to_string(), usd)]),
+            provider: Some(provider.to_string()),
+            fetched_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn streams_current_then_refreshed_tables_for_the_base() {
+        let cache = Arc::new(RateCache::new(String::new(), Duration::from_secs(600)));
+        cache.insert(rates("api.frankfurter.app", "EUR", 1.07)).await;
+
+        let mut updates = Box::pin(rate_updates(cache.clone(), "EUR".to_string()));
+        assert_eq!(updates.next().await.unwrap().rates["USD"], 1.07);
+
+        cache.insert(rates("api.frankfurter.app", "GBP", 1.27)).await;
+        cache.insert(rates(MOCK_PROVIDER, "EUR", 1.0)).await;
+        cache.insert(rates("open.er-api.com", "EUR", 1.08)).await;
+        assert_eq!(updates.next().await.unwrap().rates["USD"], 1.08);
+    }
+}
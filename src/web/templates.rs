@@ -1,73 +1,144 @@
+use askama::Template;
 use axum::response::Html;
-use super::routes::ConversionResult;
-
-// Simple template rendering function
-fn render_template(template_name: &str, replacements: &[(String, String)]) -> Html<String> {
-    // Get template content based on template name
-    let content = match template_name {
-        "conversion_result.html" => include_str!("templates/conversion_result.html"),
-        "currencies_list.html" => include_str!("templates/currencies_list.html"),
-        "error.html" => include_str!("templates/error.html"),
-        _ => return Html(format!("<p>Template not found: {}</p>", template_name)),
-    };
-    
-    let mut content = content.to_string();
-    
-    for (key, value) in replacements {
-        // Replace {{ key }} with value (without curly braces)
-        content = content.replace(&format!("{{{{ {} }}}}", key), value);
-        // Also handle the Handlebars-style {{{key}}} syntax
-        content = content.replace(&format!("{{{{{{{}}}}}}}", key), value);
-    }
-    
-    // Handle each loop for currencies
-    if template_name == "currencies_list.html" && content.contains("{{#each currencies}}") {
-        if let Some(currencies_value) = replacements.iter().find(|(k, _)| k == "currencies_json") {
-            let currencies: Vec<String> = serde_json::from_str(&currencies_value.1).unwrap_or_default();
-            let mut items_html = String::new();
-            
-            for currency in currencies {
-                items_html.push_str(&format!(
-                    "<div class=\"bg-gray-50 rounded p-2 text-sm\">\n    <span class=\"font-medium\">{}</span>\n</div>\n",
-                    currency
-                ));
-            }
-            
-            content = content.replace(
-                "{{#each currencies}}\n        <div class=\"bg-gray-50 rounded p-2 text-sm\">\n            <span class=\"font-medium\">{{this}}</span>\n        </div>\n        {{/each}}",
-                &items_html
-            );
-        }
+use super::history::HistoryEntry;
+use super::routes::{ConversionResult, CurrencyEntry, CurrencyOption, MAX_HOLDINGS_LENGTH, PortfolioView};
+
+#[derive(Template)]
+#[template(path = "index.html")]
+struct IndexTemplate<'a> {
+    favourites: &'a [CurrencyOption],
+    others: &'a [CurrencyOption],
+    /// CSP nonce for the page's inline scripts
+    nonce: &'a str,
+    /// Sent back by HTMX in the X-CSRF-Token header
+    csrf_token: &'a str,
+    /// Load the HTMX SSE extension for live conversion results
+    live_results: bool,
+}
+
+#[derive(Template)]
+#[template(path = "conversion_result.html")]
+struct ConversionResultTemplate {
+    result: ConversionResult,
+}
+
+#[derive(Template)]
+#[template(path = "live_result.html")]
+struct LiveResultTemplate {
+    result: ConversionResult,
+    url: String,
+}
+
+#[derive(Template)]
+#[template(path = "currencies_list.html")]
+struct CurrenciesListTemplate<'a> {
+    base: &'a str,
+    currencies: Vec<CurrencyEntry>,
+}
+
+#[derive(Template)]
+#[template(path = "currency_options.html")]
+struct CurrencyOptionsTemplate<'a> {
+    favourites: &'a [CurrencyOption],
+    others: &'a [CurrencyOption],
+    selected: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "history.html")]
+struct HistoryTemplate<'a> {
+    entries: &'a [HistoryEntry],
+    /// Rendered alongside a conversion result, replacing the panel out of band
+    oob: bool,
+}
+
+#[derive(Template)]
+#[template(path = "portfolio.html")]
+struct PortfolioTemplate<'a> {
+    favourites: &'a [CurrencyOption],
+    others: &'a [CurrencyOption],
+    nonce: &'a str,
+    csrf_token: &'a str,
+    max_length: usize,
+    /// Latest date the comparison picker allows (yesterday)
+    max_compare: String,
+}
+
+#[derive(Template)]
+#[template(path = "portfolio_result.html")]
+struct PortfolioResultTemplate {
+    valuation: PortfolioView,
+}
+
+#[derive(Template)]
+#[template(path = "error.html")]
+struct ErrorTemplate {
+    message: String,
+}
+
+// Render a compiled template; values are HTML-escaped by askama
+fn render_template(template: &impl Template) -> Html<String> {
+    match template.render() {
+        Ok(content) => Html(content),
+        Err(e) => Html(format!("<p>Template error: {}</p>", e)),
     }
-    
-    Html(content)
 }
 
-pub fn render_index() -> Html<String> {
-    // Embed the template directly in the binary
-    let content = include_str!("templates/index.html");
-    Html(content.to_string())
+pub fn render_index(
+    favourites: &[CurrencyOption],
+    others: &[CurrencyOption],
+    nonce: &str,
+    csrf_token: &str,
+    live_results: bool,
+) -> Html<String> {
+    render_template(&IndexTemplate { favourites, others, nonce, csrf_token, live_results })
 }
 
 pub fn render_conversion_result(result: ConversionResult) -> Html<String> {
-    let replacements = vec![
-        ("amount".to_string(), result.amount),
-        ("from".to_string(), result.from),
-        ("to".to_string(), result.to),
-        ("result".to_string(), result.result),
-        ("rate".to_string(), result.rate),
-        ("timestamp".to_string(), result.timestamp),
-    ];
-    
-    render_template("conversion_result.html", &replacements)
+    render_template(&ConversionResultTemplate { result })
+}
+
+/// A conversion result for the page, kept up to date from `live_url` when given.
+pub fn render_result_panel(result: ConversionResult, live_url: Option<String>) -> Html<String> {
+    match live_url {
+        Some(url) => render_template(&LiveResultTemplate { result, url }),
+        None => render_conversion_result(result),
+    }
+}
+
+pub fn render_currencies_list(base: &str, currencies: Vec<CurrencyEntry>) -> Html<String> {
+    render_template(&CurrenciesListTemplate { base, currencies })
+}
+
+pub fn render_currency_options(favourites: &[CurrencyOption], others: &[CurrencyOption], selected: &str) -> Html<String> {
+    render_template(&CurrencyOptionsTemplate { favourites, others, selected })
+}
+
+pub fn render_history(entries: &[HistoryEntry], oob: bool) -> Html<String> {
+    render_template(&HistoryTemplate { entries, oob })
+}
+
+pub fn render_portfolio(
+    favourites: &[CurrencyOption],
+    others: &[CurrencyOption],
+    nonce: &str,
+    csrf_token: &str,
+) -> Html<String> {
+    let yesterday = chrono::Utc::now().date_naive().pred_opt().unwrap_or_default();
+    render_template(&PortfolioTemplate {
+        favourites,
+        others,
+        nonce,
+        csrf_token,
+        max_length: MAX_HOLDINGS_LENGTH,
+        max_compare: yesterday.to_string(),
+    })
 }
 
-// Removed unused render_currencies_list function
+pub fn render_portfolio_result(valuation: PortfolioView) -> Html<String> {
+    render_template(&PortfolioResultTemplate { valuation })
+}
 
 pub fn render_error(message: String) -> Html<String> {
-    let replacements = vec![
-        ("message".to_string(), message),
-    ];
-    
-    render_template("error.html", &replacements)
+    render_template(&ErrorTemplate { message })
 }
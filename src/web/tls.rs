@@ -0,0 +1,155 @@
+//! HTTPS listener using rustls, with certificate hot-reload and an optional
+//! plain-HTTP listener that redirects to it.
+
+use anyhow::{Context, Result};
+use axum::{
+    Router,
+    extract::State,
+    http::{HeaderMap, Uri, header, uri::Authority},
+    response::Redirect,
+};
+use axum_server::{Handle, tls_rustls::RustlsConfig};
+use std::{
+    net::SocketAddr,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use super::server::shutdown_signal;
+
+/// How often the certificate and key files are checked for changes.
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Clone)]
+pub struct TlsFiles {
+    /// PEM certificate chain
+    pub cert: PathBuf,
+    /// PEM private key
+    pub key: PathBuf,
+}
+
+impl TlsFiles {
+    fn modified(&self) -> Option<(SystemTime, SystemTime)> {
+        let cert = std::fs::metadata(&self.cert).and_then(|metadata| metadata.modified()).ok()?;
+        let key = std::fs::metadata(&self.key).and_then(|metadata| metadata.modified()).ok()?;
+        Some((cert, key))
+    }
+}
+
+async fn resolve(host: &str, port: u16) -> Result<SocketAddr> {
+    tokio::net::lookup_host((host, port))
+        .await
+        .with_context(|| format!("Failed to resolve {}:{}", host, port))?
+        .next()
+        .with_context(|| format!("No addresses found for {}", host))
+}
+
+/// Reloads `config` whenever the certificate or key file changes on disk.
+/// A failed reload (e.g. a half-written renewal) keeps serving the old certificate.
+async fn watch_certificates(config: RustlsConfig, files: TlsFiles) {
+    let mut last_modified = files.modified();
+    let mut interval = tokio::time::interval(RELOAD_CHECK_INTERVAL);
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+        let modified = files.modified();
+        if modified.is_none() || modified == last_modified {
+            continue;
+        }
+
+        match config.reload_from_pem_file(&files.cert, &files.key).await {
+            Ok(()) => {
+                tracing::info!("Reloaded TLS certificate from {}", files.cert.display());
+                last_modified = modified;
+            }
+            Err(e) => tracing::error!("Failed to reload TLS certificate: {}", e),
+        }
+    }
+}
+
+async fn redirect_to_https(State(https_port): State<u16>, headers: HeaderMap, uri: Uri) -> Redirect {
+    let host = headers
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<Authority>().ok())
+        .map(|authority| authority.host().to_string())
+        .unwrap_or_else(|| "localhost".to_string());
+    let port = if https_port == 443 { String::new() } else { format!(":{}", https_port) };
+    let path = uri.path_and_query().map(|path| path.as_str()).unwrap_or("/");
+
+    Redirect::permanent(&format!("https://{}{}{}", host, port, path))
+}
+
+/// Serves `app` over HTTPS on `host:port`, optionally redirecting plain HTTP
+/// from `redirect_port`, until a shutdown signal drains both listeners.
+pub async fn serve_tls(host: &str, port: u16, files: TlsFiles, redirect_port: Option<u16>, app: Router) -> Result<()> {
+    // Several crates may enable rustls providers; pick ring explicitly
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    let config = RustlsConfig::from_pem_file(&files.cert, &files.key)
+        .await
+        .with_context(|| format!("Failed to load TLS certificate {} and key {}", files.cert.display(), files.key.display()))?;
+    let watcher = tokio::spawn(watch_certificates(config.clone(), files));
+
+    let addr = resolve(host, port).await?;
+    let handle = Handle::new();
+    let server = axum_server::bind_rustls(addr, config)
+        .handle(handle.clone())
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>());
+    let mut server = tokio::spawn(server);
+
+    let redirect_handle = Handle::new();
+    let mut redirect = match redirect_port {
+        Some(redirect_port) => {
+            let redirect_addr = resolve(host, redirect_port).await?;
+            let redirect_app = Router::new().fallback(redirect_to_https).with_state(port);
+            let redirect = axum_server::bind(redirect_addr)
+                .handle(redirect_handle.clone())
+                .serve(redirect_app.into_make_service());
+            Some((redirect_addr, tokio::spawn(redirect)))
+        }
+        None => None,
+    };
+
+    // Either a listener fails to start, or both come up and we wait for a signal
+    tokio::select! {
+        result = &mut server => {
+            result?.with_context(|| format!("Failed to bind to address {}", addr))?;
+            anyhow::bail!("Web server stopped before it started listening");
+        }
+        Some(addr) = async { handle.listening().await } => {
+            tracing::info!("Server started successfully on https://{}", addr);
+        }
+    }
+    if let Some((redirect_addr, task)) = &mut redirect {
+        tokio::select! {
+            result = task => {
+                result?.with_context(|| format!("Failed to bind to address {}", redirect_addr))?;
+                anyhow::bail!("Redirect server stopped before it started listening");
+            }
+            Some(addr) = async { redirect_handle.listening().await } => {
+                tracing::info!("Redirecting http://{} to HTTPS", addr);
+            }
+        }
+    }
+
+    tokio::select! {
+        _ = shutdown_signal() => {}
+        result = &mut server => {
+            result?.context("Web server failed")?;
+        }
+    }
+
+    handle.graceful_shutdown(None);
+    redirect_handle.graceful_shutdown(None);
+    if !server.is_finished() {
+        server.await?.context("Web server failed")?;
+    }
+    if let Some((_, task)) = redirect {
+        task.await?.context("Redirect server failed")?;
+    }
+    watcher.abort();
+
+    Ok(())
+}
@@ -0,0 +1,551 @@
+//! Outbound webhooks pushing new rate snapshots to subscribers.
+//!
+//! Subscribers register a URL for a base currency and get a secret. Each new
+//! rate publication for that base is POSTed as JSON, signed with HMAC-SHA256
+//! of the body in `X-Webhook-Signature-256: sha256=<hex>`. Failed deliveries
+//! are retried with exponential backoff, then recorded as dead letters.
+//! Destinations that resolve to non-public addresses are refused, see
+//! [`crate::outbound`].
+
+use anyhow::{Context, Result, bail};
+use chrono::{SecondsFormat, Utc};
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use reqwest::StatusCode;
+use rusqlite::{Connection, Row, params};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    hash::{DefaultHasher, Hash, Hasher},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{ExchangeRates, MOCK_PROVIDER, currency, outbound, store, token::random_hex};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS webhook_subscriptions (
+        id         INTEGER PRIMARY KEY,
+        url        TEXT    NOT NULL,
+        base       TEXT    NOT NULL,
+        secret     TEXT    NOT NULL,
+        created_at TEXT    NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS webhook_subscriptions_by_base ON webhook_subscriptions (base);
+    CREATE TABLE IF NOT EXISTS webhook_dead_letters (
+        id              INTEGER PRIMARY KEY,
+        subscription_id INTEGER NOT NULL,
+        url             TEXT    NOT NULL,
+        delivery_id     TEXT    NOT NULL,
+        payload         TEXT    NOT NULL,
+        attempts        INTEGER NOT NULL,
+        last_error      TEXT    NOT NULL,
+        failed_at       TEXT    NOT NULL
+    );
+";
+
+pub const EVENT: &str = "rates.updated";
+pub const SIGNATURE_HEADER: &str = "x-webhook-signature-256";
+pub const EVENT_HEADER: &str = "x-webhook-event";
+pub const DELIVERY_HEADER: &str = "x-webhook-delivery";
+
+/// Longest wait between two delivery attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+/// Limit on resolving and then delivering to a subscriber.
+pub const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Deserialize)]
+pub struct NewSubscription {
+    pub url: String,
+    pub base: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Subscription {
+    pub id: i64,
+    pub url: String,
+    pub base: String,
+    /// Only returned when the subscription is created
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeadLetter {
+    pub id: i64,
+    pub subscription_id: i64,
+    pub url: String,
+    pub delivery_id: String,
+    /// The undelivered JSON body
+    pub payload: serde_json::Value,
+    pub attempts: u32,
+    pub last_error: String,
+    pub failed_at: String,
+}
+
+impl DeadLetter {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let payload: String = row.get(4)?;
+        Ok(Self {
+            id: row.get(0)?,
+            subscription_id: row.get(1)?,
+            url: row.get(2)?,
+            delivery_id: row.get(3)?,
+            payload: serde_json::from_str(&payload).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(4, rusqlite::types::Type::Text, Box::new(e))
+            })?,
+            attempts: row.get(5)?,
+            last_error: row.get(6)?,
+            failed_at: row.get(7)?,
+        })
+    }
+}
+
+pub struct WebhookStore {
+    conn: Mutex<Connection>,
+}
+
+impl WebhookStore {
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::init(store::open_connection(path)?)
+    }
+
+    #[cfg(test)]
+    pub fn in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA).context("Failed to create webhook tables")?;
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Registers `subscription` with a fresh signing secret, returned only here.
+    pub fn add(&self, subscription: NewSubscription) -> Result<Subscription> {
+        let url = outbound::parse(&subscription.url).context("Invalid webhook URL")?;
+        let base = subscription.base.trim().to_uppercase();
+        if !currency::is_currency_code(&base) {
+            bail!("{:?} is not a three-letter currency code", subscription.base);
+        }
+
+        let secret = random_hex(32);
+        let created_at = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+        let conn = self.conn();
+        conn.execute(
+            "INSERT INTO webhook_subscriptions (url, base, secret, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![url.as_str(), base, secret, created_at],
+        )?;
+
+        Ok(Subscription {
+            id: conn.last_insert_rowid(),
+            url: url.to_string(),
+            base,
+            secret: Some(secret),
+            created_at,
+        })
+    }
+
+    /// All subscriptions, without their secrets.
+    pub fn list(&self) -> Result<Vec<Subscription>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare("SELECT id, url, base, created_at FROM webhook_subscriptions ORDER BY id")?;
+        let subscriptions = statement
+            .query_map([], |row| {
+                Ok(Subscription {
+                    id: row.get(0)?,
+                    url: row.get(1)?,
+                    base: row.get(2)?,
+                    secret: None,
+                    created_at: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(subscriptions)
+    }
+
+    /// Subscriptions for `base` as (id, url, secret).
+    fn for_base(&self, base: &str) -> Result<Vec<(i64, String, String)>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare("SELECT id, url, secret FROM webhook_subscriptions WHERE base = ?1")?;
+        let subscriptions = statement
+            .query_map([base], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(subscriptions)
+    }
+
+    /// Removes a subscription, returning whether it existed.
+    pub fn remove(&self, id: i64) -> Result<bool> {
+        Ok(self.conn().execute("DELETE FROM webhook_subscriptions WHERE id = ?1", [id])? > 0)
+    }
+
+    fn add_dead_letter(&self, delivery: &Delivery, attempts: u32, last_error: &str) -> Result<()> {
+        self.conn().execute(
+            "INSERT INTO webhook_dead_letters (subscription_id, url, delivery_id, payload, attempts, last_error, failed_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                delivery.subscription_id,
+                delivery.url,
+                delivery.id,
+                delivery.payload.as_str(),
+                attempts,
+                last_error,
+                Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn dead_letters(&self) -> Result<Vec<DeadLetter>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(
+            "SELECT id, subscription_id, url, delivery_id, payload, attempts, last_error, failed_at
+             FROM webhook_dead_letters ORDER BY id",
+        )?;
+        let dead_letters = statement.query_map([], DeadLetter::from_row)?.collect::<rusqlite::Result<_>>()?;
+        Ok(dead_letters)
+    }
+
+    /// Removes a dead letter, returning whether it existed.
+    pub fn remove_dead_letter(&self, id: i64) -> Result<bool> {
+        Ok(self.conn().execute("DELETE FROM webhook_dead_letters WHERE id = ?1", [id])? > 0)
+    }
+}
+
+/// Body of a `rates.updated` delivery.
+#[derive(Serialize)]
+struct RatesUpdated<'a> {
+    event: &'static str,
+    base: &'a str,
+    provider: Option<&'a str>,
+    /// Provider's publication time (Unix seconds)
+    published_at: Option<u64>,
+    date: Option<&'a str>,
+    rates: &'a HashMap<String, f64>,
+}
+
+struct Delivery {
+    id: String,
+    subscription_id: i64,
+    url: String,
+    secret: String,
+    payload: Arc<String>,
+}
+
+/// Identifies a rate table by its base, date and rates. Not the publication
+/// time: subscribers act on the rates, and a refetch with the same rates is not news.
+fn fingerprint(rates: &ExchangeRates) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    rates.base.hash(&mut hasher);
+    rates.date.hash(&mut hasher);
+    let table: BTreeMap<&String, u64> = rates.rates.iter().map(|(code, rate)| (code, rate.to_bits())).collect();
+    table.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// `sha256=<hex>` HMAC of `body` with the subscription's secret.
+pub fn signature(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+pub struct RetryPolicy {
+    /// Attempts before a delivery becomes a dead letter
+    pub max_attempts: u32,
+    /// Wait after the first failure, doubled after each further one
+    pub initial_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Wait before retry number `attempt` (1-based), with up to 25% jitter.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let base = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(MAX_BACKOFF);
+        base.mul_f64(1.0 + rand::thread_rng().gen_range(0.0..0.25))
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Delivers new rate publications to the subscribers of their base.
+pub struct Webhooks {
+    store: Arc<WebhookStore>,
+    retry: RetryPolicy,
+    /// Deliver to loopback and private addresses too
+    allow_private: bool,
+    /// Fingerprint of the table last delivered per base, so unchanged refreshes are not re-sent
+    delivered: Mutex<HashMap<String, u64>>,
+}
+
+impl Webhooks {
+    pub fn new(store: Arc<WebhookStore>, retry: RetryPolicy, allow_private: bool) -> Self {
+        Self {
+            store,
+            retry,
+            allow_private,
+            delivered: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Bases with at least one subscription, which the refresher keeps
+    /// fetching so their subscribers receive updates.
+    pub fn subscribed_bases(&self) -> Vec<String> {
+        match self.store.list() {
+            Ok(subscriptions) => {
+                let bases: BTreeSet<String> = subscriptions.into_iter().map(|subscription| subscription.base).collect();
+                bases.into_iter().collect()
+            }
+            Err(e) => {
+                tracing::warn!("Failed to list webhook subscriptions: {:#}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Sends `rates` to their base's subscribers in the background, unless
+    /// they are mock data or the same table was already sent.
+    pub fn publish(self: &Arc<Self>, rates: &ExchangeRates) {
+        let Some(base) = rates.base.as_deref() else {
+            return;
+        };
+        if rates.provider.as_deref() == Some(MOCK_PROVIDER) {
+            return;
+        }
+        let fingerprint = fingerprint(rates);
+        {
+            let mut delivered = self.delivered.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            if delivered.insert(base.to_string(), fingerprint) == Some(fingerprint) {
+                return;
+            }
+        }
+
+        let subscriptions = match self.store.for_base(base) {
+            Ok(subscriptions) if subscriptions.is_empty() => return,
+            Ok(subscriptions) => subscriptions,
+            Err(e) => {
+                tracing::warn!(base, "Failed to load webhook subscriptions: {:#}", e);
+                return;
+            }
+        };
+        let payload = match serde_json::to_string(&RatesUpdated {
+            event: EVENT,
+            base,
+            provider: rates.provider.as_deref(),
+            published_at: rates.timestamp,
+            date: rates.date.as_deref(),
+            rates: &rates.rates,
+        }) {
+            Ok(payload) => Arc::new(payload),
+            Err(e) => {
+                tracing::warn!(base, "Failed to serialize webhook payload: {}", e);
+                return;
+            }
+        };
+
+        for (subscription_id, url, secret) in subscriptions {
+            let delivery = Delivery {
+                id: random_hex(16),
+                subscription_id,
+                url,
+                secret,
+                payload: payload.clone(),
+            };
+            let webhooks = self.clone();
+            tokio::spawn(async move { webhooks.deliver(delivery).await });
+        }
+    }
+
+    async fn attempt(&self, delivery: &Delivery) -> Result<(), (String, bool)> {
+        // Resolved again for every attempt, as the subscriber's DNS may have changed since registration
+        let url = outbound::parse(&delivery.url).map_err(|e| (format!("{:#}", e), false))?;
+        let client = outbound::client(&url, self.allow_private, DELIVERY_TIMEOUT)
+            .await
+            .map_err(|e| (format!("{:#}", e), !e.is::<outbound::Refused>()))?;
+        let response = client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, EVENT)
+            .header(DELIVERY_HEADER, &delivery.id)
+            .header(SIGNATURE_HEADER, signature(&delivery.secret, delivery.payload.as_bytes()))
+            .body(delivery.payload.to_string())
+            .send()
+            .await
+            .map_err(|e| (e.to_string(), true))?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        // Other client errors will not go away by retrying
+        let retryable = status.is_server_error()
+            || status == StatusCode::REQUEST_TIMEOUT
+            || status == StatusCode::TOO_MANY_REQUESTS;
+        Err((format!("HTTP {}", status), retryable))
+    }
+
+    async fn deliver(&self, delivery: Delivery) {
+        let max_attempts = self.retry.max_attempts.max(1);
+        let mut attempt = 1;
+        loop {
+            let error = match self.attempt(&delivery).await {
+                Ok(()) => {
+                    tracing::debug!(subscription = delivery.subscription_id, attempt, "Delivered webhook");
+                    return;
+                }
+                Err((error, retryable)) if retryable && attempt < max_attempts => error,
+                Err((error, _)) => {
+                    tracing::warn!(
+                        subscription = delivery.subscription_id,
+                        attempts = attempt,
+                        "Webhook delivery failed, moved to dead letters: {}",
+                        error
+                    );
+                    if let Err(e) = self.store.add_dead_letter(&delivery, attempt, &error) {
+                        tracing::warn!("Failed to record webhook dead letter: {:#}", e);
+                    }
+                    return;
+                }
+            };
+
+            let backoff = self.retry.backoff(attempt);
+            tracing::debug!(
+                subscription = delivery.subscription_id,
+                attempt,
+                seconds = backoff.as_secs_f64(),
+                "Webhook delivery failed, retrying: {}",
+                error
+            );
+            tokio::time::sleep(backoff).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::http_sink;
+
+    fn rates(fetched_at: u64) -> ExchangeRates {
+        rates_of(fetched_at, 44.1)
+    }
+
+    fn rates_of(fetched_at: u64, uah: f64) -> ExchangeRates {
+        ExchangeRates {
+            success: true,
+            timestamp: Some(fetched_at),
+            base: Some("EUR".to_string()),
+            date: Some("2024-06-10".to_string()),
+            rates: HashMap::from([("UAH".to_string(), uah)]),
+            provider: Some("api.frankfurter.app".to_string()),
+            fetched_at: None,
+        }
+    }
+
+    /// Publisher with one EUR subscription to `url`; the local test sinks need `allow_private`.
+    fn webhooks_with(url: &str, max_attempts: u32, allow_private: bool) -> (Arc<Webhooks>, Arc<WebhookStore>, String) {
+        let store = Arc::new(WebhookStore::in_memory().unwrap());
+        let subscription = store
+            .add(NewSubscription { url: url.to_string(), base: "eur".to_string() })
+            .unwrap();
+        let retry = RetryPolicy { max_attempts, initial_backoff: Duration::from_millis(10) };
+        let webhooks = Arc::new(Webhooks::new(store.clone(), retry, allow_private));
+        (webhooks, store, subscription.secret.unwrap())
+    }
+
+    fn webhooks(url: &str, max_attempts: u32) -> (Arc<Webhooks>, Arc<WebhookStore>, String) {
+        webhooks_with(url, max_attempts, true)
+    }
+
+    async fn wait_for_dead_letters(store: &WebhookStore) -> Vec<DeadLetter> {
+        for _ in 0..100 {
+            let dead_letters = store.dead_letters().unwrap();
+            if !dead_letters.is_empty() {
+                return dead_letters;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        Vec::new()
+    }
+
+    #[tokio::test]
+    async fn delivers_signed_payload_once_per_rate_table() {
+        let (url, received) = http_sink(&[204]);
+        let (webhooks, _, secret) = webhooks(&url, 3);
+
+        // Frankfurter and currency-api report the fetch time, so refetching the same table changes it
+        webhooks.publish(&rates(1_718_000_000));
+        webhooks.publish(&rates(1_718_000_600));
+        let (request, received) = tokio::task::spawn_blocking(move || {
+            let request = received.recv_timeout(Duration::from_secs(10)).unwrap();
+            assert!(received.recv_timeout(Duration::from_millis(200)).is_err());
+            (request, received)
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(request.headers[SIGNATURE_HEADER], signature(&secret, &request.body));
+        assert_eq!(request.headers[EVENT_HEADER], EVENT);
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(body["base"], "EUR");
+        assert_eq!(body["rates"]["UAH"], 44.1);
+
+        webhooks.publish(&rates_of(1_718_001_200, 44.2));
+        let request = tokio::task::spawn_blocking(move || received.recv_timeout(Duration::from_secs(10)).unwrap())
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(body["rates"]["UAH"], 44.2);
+    }
+
+    #[tokio::test]
+    async fn retries_then_records_dead_letter() {
+        let (url, received) = http_sink(&[500, 503, 500]);
+        let (webhooks, store, _) = webhooks(&url, 3);
+
+        webhooks.publish(&rates(1_718_000_000));
+        let deliveries = tokio::task::spawn_blocking(move || {
+            (0..3)
+                .map(|_| received.recv_timeout(Duration::from_secs(10)).unwrap().headers[DELIVERY_HEADER].clone())
+                .collect::<Vec<_>>()
+        })
+        .await
+        .unwrap();
+        assert!(deliveries.iter().all(|id| *id == deliveries[0]));
+
+        let dead_letters = wait_for_dead_letters(&store).await;
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].attempts, 3);
+        assert_eq!(dead_letters[0].last_error, "HTTP 500 Internal Server Error");
+        assert_eq!(dead_letters[0].payload["base"], "EUR");
+    }
+
+    #[tokio::test]
+    async fn private_destinations_are_refused_at_delivery() {
+        let (url, received) = http_sink(&[204]);
+        let (webhooks, store, _) = webhooks_with(&url, 3, false);
+
+        webhooks.publish(&rates(1_718_000_000));
+        let dead_letters = wait_for_dead_letters(&store).await;
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].attempts, 1);
+        assert!(dead_letters[0].last_error.contains("not a public address"));
+        assert!(received.try_recv().is_err());
+    }
+}